@@ -0,0 +1,59 @@
+use iced::{
+    alignment,
+    widget::{button, container, row, text, Space},
+    Element, Length,
+};
+
+use crate::Message;
+
+/// A message shown above the panes.
+#[derive(Debug, Clone)]
+pub enum Banner {
+    Loading(String),
+    Error {
+        message: String,
+        /// The url to fetch again when retrying.
+        retry: Option<String>,
+    },
+}
+
+pub fn view(banner: &Banner) -> Element<'_, Message> {
+    let content = match banner {
+        Banner::Loading(url) => row![text(format!("Loading {url}…"))],
+        Banner::Error { message, retry } => {
+            let mut content = row![text(message), Space::with_width(Length::Fill)].spacing(10);
+            if let Some(url) = retry {
+                content = content.push(button("Retry").on_press(Message::Load(url.clone())));
+            }
+            content.push(button("Dismiss").on_press(Message::DismissBanner))
+        }
+    };
+
+    let error = matches!(banner, Banner::Error { .. });
+    container(content.align_y(alignment::Vertical::Center))
+        .padding(10)
+        .width(Length::Fill)
+        .style(move |_| styles::banner(error))
+        .into()
+}
+
+mod styles {
+    use iced::{widget::container, Background, Color};
+
+    pub fn banner(error: bool) -> container::Style {
+        let background = if error {
+            Color::from_rgb8(253, 236, 234)
+        } else {
+            Color::from_rgb8(234, 242, 251)
+        };
+        container::Style {
+            background: Some(Background::Color(background)),
+            text_color: Some(if error {
+                Color::from_rgb8(176, 42, 34)
+            } else {
+                Color::from_rgb8(45, 112, 179)
+            }),
+            ..Default::default()
+        }
+    }
+}
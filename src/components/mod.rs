@@ -1,2 +1,4 @@
+pub mod banner;
 pub mod icons;
+pub mod settings;
 pub mod sidebar;
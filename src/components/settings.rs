@@ -0,0 +1,161 @@
+use iced::{
+    alignment,
+    widget::{checkbox, column, container, row, scrollable, text, TextInput},
+    Element, Length, Padding,
+};
+
+use crate::{
+    graph::{self, Axes, AxisScale, GraphSettings, Viewport},
+    Message,
+};
+
+/// The viewport bounds as typed into the settings pane.
+#[derive(Debug, Clone, Default)]
+pub struct Bounds {
+    pub xmin: String,
+    pub xmax: String,
+    pub ymin: String,
+    pub ymax: String,
+}
+
+impl Bounds {
+    pub fn new(viewport: Viewport, axes: Axes) -> Self {
+        let x_range = viewport.xmax - viewport.xmin;
+        let y_range = viewport.ymax - viewport.ymin;
+        Self {
+            xmin: format_bound(viewport.xmin, x_range, axes.x),
+            xmax: format_bound(viewport.xmax, x_range, axes.x),
+            ymin: format_bound(viewport.ymin, y_range, axes.y),
+            ymax: format_bound(viewport.ymax, y_range, axes.y),
+        }
+    }
+
+    pub fn parse(&self) -> Option<Viewport> {
+        Some(Viewport {
+            xmin: graph::parse_bound(&self.xmin)?,
+            xmax: graph::parse_bound(&self.xmax)?,
+            ymin: graph::parse_bound(&self.ymin)?,
+            ymax: graph::parse_bound(&self.ymax)?,
+        })
+    }
+}
+
+/// Rounds `value` to a thousandth of `range`, or of itself on a log axis, where the range says
+/// little about the size of the bounds.
+fn format_bound(value: f32, range: f32, scale: AxisScale) -> String {
+    let magnitude = match scale {
+        AxisScale::Linear => range,
+        AxisScale::Log => value.abs(),
+    };
+    let decimals = (3.0 - magnitude.log10().floor()).clamp(0.0, 10.0) as usize;
+    format!("{value:.decimals$}")
+}
+
+pub fn view<'a>(settings: &'a GraphSettings, bounds: &Bounds) -> Element<'a, Message> {
+    let toggle = |label: &'a str, checked: bool, set: fn(&mut GraphSettings, bool)| {
+        checkbox(label, checked)
+            .on_toggle(move |checked| {
+                let mut settings = settings.clone();
+                set(&mut settings, checked);
+                Message::SettingsChanged(settings)
+            })
+            .size(16)
+            .text_size(14)
+    };
+    let is_log = |scale: AxisScale| scale == AxisScale::Log;
+
+    let toggles = column![
+        toggle("Grid", settings.show_grid, |s, show| s.show_grid = show),
+        toggle("X axis", settings.show_x_axis, |s, show| s.show_x_axis =
+            show),
+        toggle("Y axis", settings.show_y_axis, |s, show| s.show_y_axis =
+            show),
+        toggle("X axis numbers", settings.show_x_labels, |s, show| {
+            s.show_x_labels = show
+        }),
+        toggle("Y axis numbers", settings.show_y_labels, |s, show| {
+            s.show_y_labels = show
+        }),
+        toggle("Logarithmic x axis", is_log(settings.axes.x), |s, log| {
+            s.axes.x = axis_scale(log)
+        }),
+        toggle("Logarithmic y axis", is_log(settings.axes.y), |s, log| {
+            s.axes.y = axis_scale(log)
+        }),
+    ]
+    .spacing(6);
+
+    let title = |placeholder: &str, value: &str, set: fn(&mut GraphSettings, String)| {
+        TextInput::new(placeholder, value)
+            .on_input(move |value| {
+                let mut settings = settings.clone();
+                set(&mut settings, value);
+                Message::SettingsChanged(settings)
+            })
+            .size(14)
+            .padding(Padding::from([2, 5]))
+    };
+    let titles = column![
+        title("x axis title", &settings.x_title, |s, t| s.x_title = t),
+        title("y axis title", &settings.y_title, |s, t| s.y_title = t),
+    ]
+    .spacing(6);
+
+    let view = column![
+        text("Graph settings").size(18),
+        bounds_view(bounds),
+        toggles,
+        titles
+    ]
+    .spacing(12)
+    .padding(10);
+
+    container(scrollable(view))
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .into()
+}
+
+/// Bounds are applied when enter is pressed, so half typed numbers don't move the view.
+fn bounds_view<'a>(bounds: &Bounds) -> Element<'a, Message> {
+    let input = |value: &str, set: fn(&mut Bounds, String)| {
+        let bounds = bounds.clone();
+        TextInput::new("", value)
+            .on_input(move |value| {
+                let mut bounds = bounds.clone();
+                set(&mut bounds, value);
+                Message::BoundsChanged(bounds)
+            })
+            .on_submit(Message::BoundsSubmitted)
+            .size(14)
+            .padding(Padding::from([2, 5]))
+            .width(Length::Fixed(80.0))
+    };
+
+    column![
+        row![
+            input(&bounds.xmin, |b, v| b.xmin = v),
+            text("≤ x ≤").size(14),
+            input(&bounds.xmax, |b, v| b.xmax = v),
+        ]
+        .spacing(5)
+        .align_y(alignment::Vertical::Center),
+        row![
+            input(&bounds.ymin, |b, v| b.ymin = v),
+            text("≤ y ≤").size(14),
+            input(&bounds.ymax, |b, v| b.ymax = v),
+        ]
+        .spacing(5)
+        .align_y(alignment::Vertical::Center),
+    ]
+    .spacing(6)
+    .into()
+}
+
+fn axis_scale(log: bool) -> AxisScale {
+    if log {
+        AxisScale::Log
+    } else {
+        AxisScale::Linear
+    }
+}
@@ -4,22 +4,51 @@ use desmoxide::graph::expressions::ExpressionId;
 use iced::{
     alignment, mouse,
     widget::{
-        column, container, mouse_area, row, scrollable, text, text::LineHeight, text_input,
-        text_input::Id, tooltip, TextInput,
+        button, column, container, mouse_area, row, scrollable, slider, text, text::LineHeight,
+        text_input, text_input::Id, tooltip, TextInput,
     },
     Color, Element, Length, Padding,
 };
 
 use super::icons;
-use crate::Message;
+use crate::{
+    graph::Domain,
+    style::{LineStyle, Style, PALETTE},
+    variables::{self, LoopMode, Slider},
+    ExpressionState, Message,
+};
+
+/// An expression being dragged to a new position in the list.
+#[derive(Debug, Clone, Copy)]
+pub struct Drag {
+    pub id: ExpressionId,
+    /// The expression it would take the place of if dropped now.
+    pub over: ExpressionId,
+}
 
 pub fn view<'element>(
     equations: &'element HashMap<ExpressionId, String>,
-    errors: &'element HashMap<ExpressionId, String>,
+    order: &'element [ExpressionId],
+    state: &'element ExpressionState,
     shown_error: &Option<ExpressionId>,
+    style_editor: &Option<ExpressionId>,
+    drag: &Option<Drag>,
 ) -> Element<'element, crate::Message> {
-    let mut elements = equations
+    let ExpressionState {
+        errors,
+        draw_errors,
+        domains,
+        sliders,
+        animations,
+        kinds,
+        styles: expr_styles,
+        hidden,
+        ..
+    } = state;
+    let draw_errors = draw_errors.borrow();
+    let mut elements = order
         .iter()
+        .filter_map(|i| Some((i, equations.get(i)?)))
         .map(|(i, equation)| {
             let input = TextInput::new("", equation)
                 .on_input(move |s| Message::EquationChanged(*i, s))
@@ -34,7 +63,9 @@ pub fn view<'element>(
                 .id(Id::new(format!("equation_{}", i.0)))
                 .width(Length::Fill);
 
-            let show_err = if errors.get(i).is_some() {
+            let style = expr_styles.get(i).copied().unwrap_or_default();
+            let error = errors.get(i).or_else(|| draw_errors.get(i)).cloned();
+            let show_err = if error.is_some() {
                 mouse_area(
                     container(icons::error().size(20))
                         .align_x(alignment::Horizontal::Center)
@@ -46,36 +77,99 @@ pub fn view<'element>(
                 .interaction(mouse::Interaction::Grab)
                 .on_exit(Message::ShowError(None))
             } else {
+                let visible = !hidden.contains(i);
                 mouse_area(
-                    container("")
-                        .width(Length::Fixed(35.0))
-                        .height(Length::Fixed(50.0)),
+                    container(
+                        container("")
+                            .width(Length::Fixed(20.0))
+                            .height(Length::Fixed(20.0))
+                            .style(move |_| styles::visibility_toggle(style.color, visible)),
+                    )
+                    .align_x(alignment::Horizontal::Center)
+                    .align_y(alignment::Vertical::Center)
+                    .width(Length::Fixed(35.0))
+                    .height(Length::Fixed(50.0)),
                 )
+                .on_press(Message::ToggleHidden(*i))
+                .interaction(mouse::Interaction::Pointer)
                 .on_enter(Message::ShowError(Some(*i)))
                 .on_exit(Message::ShowError(None))
             };
 
-            let left: Element<crate::Message> = if let Some(i) = *shown_error {
-                if let Some(err) = errors.get(&i) {
-                    tooltip(
-                        show_err,
-                        container(text(err).style(|_| text::Style {
-                            color: Some(Color::WHITE),
-                        }))
-                        .padding(5)
-                        .width(Length::Shrink)
-                        .height(Length::Shrink)
-                        .style(styles::floating_box),
-                        tooltip::Position::Bottom,
-                    )
-                    .into()
-                } else {
-                    show_err.into()
-                }
-            } else {
-                show_err.into()
+            let left: Element<crate::Message> = match error {
+                Some(err) if *shown_error == Some(*i) => tooltip(
+                    show_err,
+                    container(text(err).style(|_| text::Style {
+                        color: Some(Color::WHITE),
+                    }))
+                    .padding(5)
+                    .width(Length::Shrink)
+                    .height(Length::Shrink)
+                    .style(styles::floating_box),
+                    tooltip::Position::Bottom,
+                )
+                .into(),
+                _ => show_err.into(),
             };
-            row![left, input].into()
+            let editing = *style_editor == Some(*i);
+            let swatch = mouse_area(
+                container(
+                    container("")
+                        .width(Length::Fixed(20.0))
+                        .height(Length::Fixed(20.0))
+                        .style(move |_| styles::swatch(style.color)),
+                )
+                .align_y(alignment::Vertical::Center)
+                .padding(Padding::from([0, 8]))
+                .height(Length::Fixed(50.0)),
+            )
+            .on_press(Message::EditStyle((!editing).then_some(*i)))
+            .interaction(mouse::Interaction::Pointer);
+
+            let handle = mouse_area(
+                container(text("≡").size(20))
+                    .align_y(alignment::Vertical::Center)
+                    .padding(Padding::from([0, 6]))
+                    .height(Length::Fixed(50.0)),
+            )
+            .on_press(Message::DragStart(*i))
+            .interaction(mouse::Interaction::Grab);
+
+            let delete = mouse_area(
+                container(text("×").size(20))
+                    .align_y(alignment::Vertical::Center)
+                    .padding(Padding::from([0, 6]))
+                    .height(Length::Fixed(50.0)),
+            )
+            .on_press(Message::EquationRemoved(*i))
+            .interaction(mouse::Interaction::Pointer);
+
+            let mut rows: Vec<Element<Message>> =
+                vec![row![left, input, swatch, handle, delete].into()];
+            let parameter = kinds.get(i).and_then(|kind| kind.parameter());
+            if let (Some(domain), Some(parameter)) = (domains.get(i), parameter) {
+                rows.push(domain_view(*i, domain, parameter));
+            }
+            // Sliders follow desmoxide: an assignment gets one once it compiles, and undefined
+            // variables are only offered one when desmoxide rejected the expression.
+            if errors.contains_key(i) {
+                let undefined = variables::undefined(equation, equations);
+                if !undefined.is_empty() {
+                    rows.push(add_sliders_view(undefined));
+                }
+            } else if let Some((name, value)) = variables::number_assignment(equation) {
+                if let Some(bounds) = sliders.get(i) {
+                    let playing = animations.contains_key(i);
+                    rows.push(slider_view(*i, name, value, bounds, playing));
+                }
+            }
+            if editing {
+                rows.push(style_view(*i, style));
+            }
+            let drop_target = drag.is_some_and(|drag| drag.over == *i && drag.id != *i);
+            mouse_area(container(column(rows)).style(move |_| styles::row(drop_target)))
+                .on_enter(Message::DragHover(*i))
+                .into()
         })
         .collect::<Vec<Element<crate::Message>>>();
 
@@ -95,6 +189,12 @@ pub fn view<'element>(
     let sidebar = scrollable(column(elements))
         .width(Length::Fill)
         .height(Length::Fill);
+    // Drops are caught by `Somsed::subscription`, wherever the mouse is released.
+    let sidebar = mouse_area(sidebar).interaction(if drag.is_some() {
+        mouse::Interaction::Grabbing
+    } else {
+        mouse::Interaction::default()
+    });
 
     let view = container(sidebar)
         .width(Length::Fill)
@@ -104,6 +204,251 @@ pub fn view<'element>(
     view.into()
 }
 
+fn domain_view<'a>(
+    i: ExpressionId,
+    domain: &'a Domain,
+    parameter: &'static str,
+) -> Element<'a, Message> {
+    let bound = |value: &str| {
+        TextInput::new("", value)
+            .size(16)
+            .padding(Padding::from([2, 5]))
+            .width(Length::Fixed(60.0))
+    };
+
+    row![
+        bound(&domain.min).on_input(move |min| Message::DomainChanged(
+            i,
+            Domain {
+                min,
+                max: domain.max.clone(),
+            }
+        )),
+        text(format!("≤ {parameter} ≤")).size(16),
+        bound(&domain.max).on_input(move |max| Message::DomainChanged(
+            i,
+            Domain {
+                min: domain.min.clone(),
+                max,
+            }
+        )),
+    ]
+    .spacing(5)
+    .align_y(alignment::Vertical::Center)
+    .padding(Padding {
+        top: 0.0,
+        bottom: 5.0,
+        right: 0.0,
+        left: 35.0,
+    })
+    .into()
+}
+
+fn slider_view<'a>(
+    i: ExpressionId,
+    name: String,
+    value: f32,
+    bounds: &'a Slider,
+    playing: bool,
+) -> Element<'a, Message> {
+    let bound = |value: &str, set: fn(&mut Slider, String)| {
+        TextInput::new("", value)
+            .on_input(move |value| {
+                let mut bounds = bounds.clone();
+                set(&mut bounds, value);
+                Message::SliderChanged(i, bounds)
+            })
+            .size(14)
+            .padding(Padding::from([2, 5]))
+            .width(Length::Fixed(50.0))
+    };
+
+    let mut rows: Vec<Element<Message>> = Vec::new();
+    // Bounds that don't parse, or are the wrong way around, leave only the inputs to fix them.
+    if let Some((min, max)) = bounds.range() {
+        let step = bounds.step().unwrap_or((max - min) / 1000.0);
+        let play = button(text(if playing { "⏸" } else { "▶" }).size(14))
+            .on_press(Message::TogglePlaying(i))
+            .style(button::secondary)
+            .padding(Padding::from([2, 8]));
+        rows.push(
+            row![
+                play,
+                slider(min..=max, value.clamp(min, max), move |value| {
+                    Message::SliderMoved(i, value)
+                })
+                .step(step),
+            ]
+            .spacing(6)
+            .align_y(alignment::Vertical::Center)
+            .into(),
+        );
+    }
+    rows.push(
+        row![
+            bound(&bounds.min, |b, min| b.min = min),
+            text(format!("≤ {} ≤", variables::display(&name))).size(14),
+            bound(&bounds.max, |b, max| b.max = max),
+            text("step").size(14),
+            bound(&bounds.step, |b, step| b.step = step),
+        ]
+        .spacing(5)
+        .align_y(alignment::Vertical::Center)
+        .into(),
+    );
+    if playing {
+        rows.push(playback_view(i, bounds));
+    }
+
+    column(rows)
+        .spacing(6)
+        .padding(Padding {
+            top: 0.0,
+            bottom: 8.0,
+            right: 10.0,
+            left: 35.0,
+        })
+        .into()
+}
+
+/// How a playing slider loops, and how fast.
+fn playback_view<'a>(i: ExpressionId, bounds: &Slider) -> Element<'a, Message> {
+    let choice = |label: &'a str, selected: bool, slider: Slider| -> Element<'a, Message> {
+        button(text(label).size(12))
+            .on_press(Message::SliderChanged(i, slider))
+            .style(if selected {
+                button::primary
+            } else {
+                button::secondary
+            })
+            .padding(Padding::from([2, 6]))
+            .into()
+    };
+
+    let modes = LoopMode::ALL.into_iter().map(|mode| {
+        let label = match mode {
+            LoopMode::BackAndForth => "⇄",
+            LoopMode::Loop => "↻",
+            LoopMode::Once => "→",
+        };
+        let slider = Slider {
+            mode,
+            ..bounds.clone()
+        };
+        choice(label, mode == bounds.mode, slider)
+    });
+    let speeds = [
+        (0.25, "¼×"),
+        (0.5, "½×"),
+        (1.0, "1×"),
+        (2.0, "2×"),
+        (4.0, "4×"),
+    ]
+    .into_iter()
+    .map(|(speed, label)| {
+        let slider = Slider {
+            speed,
+            ..bounds.clone()
+        };
+        choice(label, speed == bounds.speed, slider)
+    });
+
+    row(modes.chain(speeds))
+        .spacing(4)
+        .align_y(alignment::Vertical::Center)
+        .into()
+}
+
+/// Buttons adding a slider for each variable nothing defines.
+fn add_sliders_view<'a>(names: Vec<String>) -> Element<'a, Message> {
+    let label: Element<Message> = text("add slider:").size(14).into();
+    let buttons = names.into_iter().map(|name| {
+        button(text(variables::display(&name)).size(14))
+            .on_press(Message::EquationAdded(format!("{name}=1")))
+            .style(button::secondary)
+            .padding(Padding::from([2, 8]))
+            .into()
+    });
+    row(std::iter::once(label).chain(buttons))
+        .spacing(6)
+        .align_y(alignment::Vertical::Center)
+        .padding(Padding {
+            top: 0.0,
+            bottom: 8.0,
+            right: 10.0,
+            left: 35.0,
+        })
+        .into()
+}
+
+fn style_view<'a>(i: ExpressionId, style: Style) -> Element<'a, Message> {
+    let colors = row(PALETTE.into_iter().map(|color| {
+        mouse_area(
+            container("")
+                .width(Length::Fixed(24.0))
+                .height(Length::Fixed(24.0))
+                .style(move |_| styles::swatch(color)),
+        )
+        .on_press(Message::StyleChanged(i, Style { color, ..style }))
+        .interaction(mouse::Interaction::Pointer)
+        .into()
+    }))
+    .spacing(6);
+
+    let line_styles = row(LineStyle::ALL.into_iter().map(|line_style| {
+        let label = match line_style {
+            LineStyle::Solid => "Solid",
+            LineStyle::Dashed => "Dashed",
+            LineStyle::Dotted => "Dotted",
+        };
+        button(text(label).size(14))
+            .on_press(Message::StyleChanged(
+                i,
+                Style {
+                    line_style,
+                    ..style
+                },
+            ))
+            .style(if line_style == style.line_style {
+                button::primary
+            } else {
+                button::secondary
+            })
+            .into()
+    }))
+    .spacing(6);
+
+    let width = row![
+        text("Width").size(14).width(Length::Fixed(60.0)),
+        slider(1.0..=10.0, style.width, move |width| {
+            Message::StyleChanged(i, Style { width, ..style })
+        })
+        .step(0.5),
+    ]
+    .spacing(6)
+    .align_y(alignment::Vertical::Center);
+
+    let opacity = row![
+        text("Opacity").size(14).width(Length::Fixed(60.0)),
+        slider(0.0..=1.0, style.opacity, move |opacity| {
+            Message::StyleChanged(i, Style { opacity, ..style })
+        })
+        .step(0.05),
+    ]
+    .spacing(6)
+    .align_y(alignment::Vertical::Center);
+
+    column![colors, line_styles, width, opacity]
+        .spacing(8)
+        .padding(Padding {
+            top: 0.0,
+            bottom: 10.0,
+            right: 10.0,
+            left: 35.0,
+        })
+        .into()
+}
+
 mod styles {
     use iced::{widget::container, Border, Color, Shadow, Theme, Vector};
 
@@ -118,6 +463,43 @@ mod styles {
         }
     }
 
+    /// Rows are outlined while something is dragged over them.
+    pub fn row(drop_target: bool) -> container::Style {
+        container::Style {
+            border: Border {
+                width: if drop_target { 2.0 } else { 0.0 },
+                color: Color::from_rgb8(45, 112, 179),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    pub fn swatch(color: Color) -> container::Style {
+        container::Style {
+            background: Some(iced::Background::Color(color)),
+            border: Border {
+                radius: 12.0.into(),
+                width: 0.0,
+                color,
+            },
+            ..Default::default()
+        }
+    }
+
+    /// A filled circle for visible expressions, and an outline for hidden ones.
+    pub fn visibility_toggle(color: Color, visible: bool) -> container::Style {
+        container::Style {
+            background: visible.then_some(iced::Background::Color(color)),
+            border: Border {
+                radius: 10.0.into(),
+                width: 2.0,
+                color,
+            },
+            ..Default::default()
+        }
+    }
+
     pub fn floating_box(_: &Theme) -> container::Style {
         container::Style {
             background: Some(iced::Background::Color(Color::from_rgb8(102, 102, 102))),
@@ -0,0 +1,316 @@
+use std::collections::{HashMap, HashSet};
+
+use desmoxide::{
+    graph::expressions::ExpressionId,
+    interop::{Expression, Graph},
+};
+use serde_json::{json, Map, Value};
+
+use crate::{
+    graph::{Axes, AxisScale, Domain, GraphSettings, Viewport},
+    style::{self, LineStyle, Style},
+    variables::{LoopMode, Slider},
+};
+
+/// Milliseconds Desmos takes to play a slider at normal speed.
+const ANIMATION_PERIOD: f32 = 4000.0;
+
+/// The parts of a Desmos graph state Somsed understands.
+#[derive(Debug, Default)]
+pub struct Document {
+    /// Expressions in the order they appear in the graph state.
+    pub expressions: Vec<(ExpressionId, String)>,
+    pub domains: HashMap<ExpressionId, Domain>,
+    pub sliders: HashMap<ExpressionId, Slider>,
+    pub styles: HashMap<ExpressionId, Style>,
+    pub hidden: HashSet<ExpressionId>,
+    pub viewport: Option<Viewport>,
+    /// Whether the viewport is fitted without stretching either axis.
+    pub square_axes: bool,
+    pub settings: GraphSettings,
+    /// The graph state this was read from, so fields Somsed doesn't know about survive a save.
+    pub state: Value,
+}
+
+impl Document {
+    pub fn from_json(state: Value) -> serde_json::Result<Self> {
+        let graph: Graph = serde_json::from_value(state.clone())?;
+        let expressions = graph
+            .exprs()
+            .into_iter()
+            .filter_map(|expr| match expr {
+                Expression::Expression { id, latex, .. } => latex
+                    .as_ref()
+                    .map(|latex| (ExpressionId(*id), latex.clone())),
+                _ => None,
+            })
+            .collect();
+
+        let domains = expression_list(&state)
+            .filter_map(|expr| {
+                let (domain, default) = match expr.get("polarDomain") {
+                    Some(domain) if is_polar(expr) => (domain, Domain::polar()),
+                    _ => (expr.get("parametricDomain")?, Domain::default()),
+                };
+                let bound =
+                    |key: &str, default: String| match domain.get(key).and_then(Value::as_str) {
+                        Some(bound) if !bound.is_empty() => bound.to_string(),
+                        _ => default,
+                    };
+                Some((
+                    expression_id(expr)?,
+                    Domain {
+                        min: bound("min", default.min),
+                        max: bound("max", default.max),
+                    },
+                ))
+            })
+            .collect();
+
+        let sliders = expression_list(&state)
+            .filter_map(|expr| Some((expression_id(expr)?, parse_slider(expr.get("slider")?))))
+            .collect();
+
+        let styles = expression_list(&state)
+            .enumerate()
+            .filter_map(|(n, expr)| Some((expression_id(expr)?, parse_style(expr, n))))
+            .collect();
+
+        let hidden = expression_list(&state)
+            .filter(|expr| expr.get("hidden").and_then(Value::as_bool) == Some(true))
+            .filter_map(expression_id)
+            .collect();
+
+        let viewport = parse_viewport(&state["graph"]["viewport"]);
+        let square_axes = state["graph"]["squareAxes"].as_bool().unwrap_or(true);
+        let settings = parse_settings(&state["graph"]);
+
+        Ok(Self {
+            expressions,
+            domains,
+            sliders,
+            styles,
+            hidden,
+            viewport,
+            square_axes,
+            settings,
+            state,
+        })
+    }
+
+    /// Writes the document back out as a Desmos graph state.
+    pub fn to_json(&self) -> Value {
+        let mut state = match &self.state {
+            Value::Object(_) => self.state.clone(),
+            _ => json!({
+                "version": 11,
+                "graph": {},
+                "expressions": { "list": [] },
+            }),
+        };
+
+        let mut previous: HashMap<ExpressionId, Map<String, Value>> = HashMap::new();
+        for expr in expression_list(&self.state) {
+            if let (Some(id), Value::Object(fields)) = (expression_id(expr), expr) {
+                if is_edited(expr) {
+                    previous.insert(id, fields.clone());
+                }
+            }
+        }
+
+        let mut expressions = self.expressions.iter().map(|(id, latex)| {
+            let mut expr = previous.remove(id).unwrap_or_default();
+            expr.insert("type".into(), "expression".into());
+            expr.insert("id".into(), id.0.to_string().into());
+            expr.insert("latex".into(), latex.clone().into());
+            expr.insert("hidden".into(), self.hidden.contains(id).into());
+            if let Some(style) = self.styles.get(id) {
+                expr.insert("color".into(), style::format_color(style.color).into());
+                expr.insert("lineStyle".into(), style.line_style.to_desmos().into());
+                expr.insert("lineWidth".into(), style.width.to_string().into());
+                expr.insert("lineOpacity".into(), style.opacity.to_string().into());
+            }
+            if let Some(domain) = self.domains.get(id) {
+                let key = if crate::graph::polar_body(latex).is_some() {
+                    "polarDomain"
+                } else {
+                    "parametricDomain"
+                };
+                expr.insert(key.into(), json!({ "min": domain.min, "max": domain.max }));
+            }
+            if let Some(slider) = self.sliders.get(id) {
+                // Keep the fields of the slider Somsed doesn't edit, like whether it's playing.
+                let mut fields = match expr.remove("slider") {
+                    Some(Value::Object(fields)) => fields,
+                    _ => Map::new(),
+                };
+                fields.insert("hardMin".into(), true.into());
+                fields.insert("hardMax".into(), true.into());
+                fields.insert("min".into(), slider.min.clone().into());
+                fields.insert("max".into(), slider.max.clone().into());
+                fields.insert("step".into(), slider.step.clone().into());
+                fields.insert("loopMode".into(), slider.mode.to_desmos().into());
+                fields.insert(
+                    "animationPeriod".into(),
+                    (ANIMATION_PERIOD / slider.speed).round().into(),
+                );
+                expr.insert("slider".into(), Value::Object(fields));
+            }
+            Value::Object(expr)
+        });
+
+        // Expressions take the places of the ones that were read, in their current order, so
+        // folders, notes and everything else Somsed doesn't edit stay where they were. The ones
+        // that were deleted leave their place empty, new ones go at the end.
+        let mut list: Vec<Value> = Vec::new();
+        for expr in expression_list(&self.state) {
+            if is_edited(expr) {
+                list.extend(expressions.next());
+            } else {
+                list.push(expr.clone());
+            }
+        }
+        list.extend(expressions);
+
+        state["expressions"]["list"] = Value::Array(list);
+        if let Some(viewport) = self.viewport {
+            state["graph"]["viewport"] = json!({
+                "xmin": viewport.xmin,
+                "ymin": viewport.ymin,
+                "xmax": viewport.xmax,
+                "ymax": viewport.ymax,
+            });
+            state["graph"]["squareAxes"] = self.square_axes.into();
+        }
+
+        let graph = &mut state["graph"];
+        let settings = &self.settings;
+        graph["showGrid"] = settings.show_grid.into();
+        graph["showXAxis"] = settings.show_x_axis.into();
+        graph["showYAxis"] = settings.show_y_axis.into();
+        graph["xAxisNumbers"] = settings.show_x_labels.into();
+        graph["yAxisNumbers"] = settings.show_y_labels.into();
+        graph["xAxisLabel"] = settings.x_title.clone().into();
+        graph["yAxisLabel"] = settings.y_title.clone().into();
+        graph["xAxisScale"] = settings.axes.x.to_desmos().into();
+        graph["yAxisScale"] = settings.axes.y.to_desmos().into();
+        state
+    }
+}
+
+fn expression_list(state: &Value) -> impl Iterator<Item = &Value> {
+    state["expressions"]["list"]
+        .as_array()
+        .into_iter()
+        .flatten()
+}
+
+fn is_expression(expr: &Value) -> bool {
+    matches!(
+        expr.get("type").and_then(Value::as_str),
+        None | Some("expression")
+    )
+}
+
+/// Whether `expr` is one of the expressions [`Document::from_json`] reads, rather than something
+/// kept as it is.
+fn is_edited(expr: &Value) -> bool {
+    is_expression(expr)
+        && expr.get("latex").is_some_and(Value::is_string)
+        && expression_id(expr).is_some()
+}
+
+fn expression_id(expr: &Value) -> Option<ExpressionId> {
+    Some(ExpressionId(expr.get("id")?.as_str()?.parse().ok()?))
+}
+
+fn is_polar(expr: &Value) -> bool {
+    expr.get("latex")
+        .and_then(Value::as_str)
+        .is_some_and(|latex| crate::graph::polar_body(latex).is_some())
+}
+
+/// Reads the `color`, `lineStyle`, `lineWidth` and `lineOpacity` fields of an expression, falling
+/// back to the palette color of the `n`th expression.
+fn parse_style(expr: &Value, n: usize) -> Style {
+    let default = Style::nth(n);
+    // Desmos stores most numbers as latex strings.
+    let number = |key: &str| match expr.get(key)? {
+        Value::String(s) => s.parse().ok(),
+        value => value.as_f64().map(|f| f as f32),
+    };
+    Style {
+        color: expr
+            .get("color")
+            .and_then(Value::as_str)
+            .and_then(style::parse_color)
+            .unwrap_or(default.color),
+        width: number("lineWidth").unwrap_or(default.width),
+        line_style: expr
+            .get("lineStyle")
+            .and_then(Value::as_str)
+            .and_then(LineStyle::from_desmos)
+            .unwrap_or(default.line_style),
+        opacity: number("lineOpacity").unwrap_or(default.opacity),
+    }
+}
+
+/// Reads the `slider` field of an expression. Anything Desmos left out is the default.
+fn parse_slider(slider: &Value) -> Slider {
+    let default = Slider::default();
+    let bound = |key: &str, default: String| match slider.get(key).and_then(Value::as_str) {
+        Some(bound) if !bound.is_empty() => bound.to_string(),
+        _ => default,
+    };
+    Slider {
+        min: bound("min", default.min),
+        max: bound("max", default.max),
+        step: bound("step", default.step),
+        mode: slider
+            .get("loopMode")
+            .and_then(Value::as_str)
+            .and_then(LoopMode::from_desmos)
+            .unwrap_or(default.mode),
+        speed: slider
+            .get("animationPeriod")
+            .and_then(Value::as_f64)
+            .filter(|period| *period > 0.0)
+            .map_or(default.speed, |period| ANIMATION_PERIOD / period as f32),
+    }
+}
+
+fn parse_viewport(viewport: &Value) -> Option<Viewport> {
+    let bound = |key: &str| Some(viewport.get(key)?.as_f64()? as f32);
+    Some(Viewport {
+        xmin: bound("xmin")?,
+        ymin: bound("ymin")?,
+        xmax: bound("xmax")?,
+        ymax: bound("ymax")?,
+    })
+}
+
+/// Reads the fields of `graph` the settings pane edits.
+fn parse_settings(graph: &Value) -> GraphSettings {
+    let default = GraphSettings::default();
+    let flag = |key: &str| graph[key].as_bool();
+    let title = |key: &str| graph[key].as_str().unwrap_or_default().to_string();
+    let scale = |key: &str| {
+        graph[key]
+            .as_str()
+            .and_then(AxisScale::from_desmos)
+            .unwrap_or_default()
+    };
+    GraphSettings {
+        axes: Axes {
+            x: scale("xAxisScale"),
+            y: scale("yAxisScale"),
+        },
+        show_grid: flag("showGrid").unwrap_or(default.show_grid),
+        show_x_axis: flag("showXAxis").unwrap_or(default.show_x_axis),
+        show_y_axis: flag("showYAxis").unwrap_or(default.show_y_axis),
+        show_x_labels: flag("xAxisNumbers").unwrap_or(default.show_x_labels),
+        show_y_labels: flag("yAxisNumbers").unwrap_or(default.show_y_labels),
+        x_title: title("xAxisLabel"),
+        y_title: title("yAxisLabel"),
+    }
+}
@@ -0,0 +1,72 @@
+use std::path::PathBuf;
+
+use serde_json::Value;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Error)]
+pub enum FileError {
+    #[error("no file was picked")]
+    DialogClosed,
+    #[error("failed to access {path}: {message}")]
+    Io { path: PathBuf, message: String },
+    #[error("{path} is not a valid graph: {message}")]
+    Json { path: PathBuf, message: String },
+}
+
+/// Reads a Desmos graph state from disk.
+pub fn read(path: PathBuf) -> Result<Value, FileError> {
+    let text = std::fs::read_to_string(&path).map_err(|e| FileError::Io {
+        path: path.clone(),
+        message: e.to_string(),
+    })?;
+    serde_json::from_str(&text).map_err(|e| FileError::Json {
+        path,
+        message: e.to_string(),
+    })
+}
+
+/// Asks for a graph to open and reads it.
+pub async fn open() -> Result<(PathBuf, Value), FileError> {
+    let path = pick(rfd::AsyncFileDialog::new().pick_file().await)?;
+    let state = read(path.clone())?;
+    Ok((path, state))
+}
+
+/// Writes `state` to `path`, asking where to save it first if there is no path yet.
+pub async fn save(path: Option<PathBuf>, state: Value) -> Result<PathBuf, FileError> {
+    let path = match path {
+        Some(path) => path,
+        None => pick(
+            rfd::AsyncFileDialog::new()
+                .set_file_name("graph.json")
+                .save_file()
+                .await,
+        )?,
+    };
+
+    let text = serde_json::to_string_pretty(&state).map_err(|e| FileError::Json {
+        path: path.clone(),
+        message: e.to_string(),
+    })?;
+    std::fs::write(&path, text).map_err(|e| FileError::Io {
+        path: path.clone(),
+        message: e.to_string(),
+    })?;
+    Ok(path)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn pick(handle: Option<rfd::FileHandle>) -> Result<PathBuf, FileError> {
+    let handle = handle.ok_or(FileError::DialogClosed)?;
+    Ok(handle.path().to_path_buf())
+}
+
+/// Browsers don't expose paths, so there is nothing to read from or write back to.
+#[cfg(target_arch = "wasm32")]
+fn pick(handle: Option<rfd::FileHandle>) -> Result<PathBuf, FileError> {
+    let handle = handle.ok_or(FileError::DialogClosed)?;
+    Err(FileError::Io {
+        path: PathBuf::from(handle.file_name()),
+        message: "local files are not supported in the browser".to_string(),
+    })
+}
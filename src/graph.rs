@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{cell::RefCell, collections::HashMap};
 
 use anyhow::{anyhow, Result};
 use desmoxide::{
@@ -7,89 +7,628 @@ use desmoxide::{
 };
 use iced::{
     event::Status,
+    keyboard,
     mouse::{self, Cursor},
     widget::canvas::{event, Cache, Event, Frame, Geometry, Path, Program, Stroke},
-    Color, Point, Size, Theme, Vector,
+    Point, Size, Theme, Vector,
 };
 
-use crate::Message;
+use crate::{
+    style::{LineStyle, Style},
+    ExpressionState, Message,
+};
 
-pub struct GraphRenderer<'a> {
-    scale: f32,
-    mid: Vector,
-    resolution: u32,
+mod analysis;
+mod axes;
+mod contour;
+mod grid;
+mod inequality;
+mod sampling;
+mod trace;
+
+pub use analysis::Analysis;
+pub use axes::{Axes, AxisScale};
+pub use inequality::{Comparison, Inequality};
+pub use trace::Trace;
+
+/// Size in pixels of the coarse grid relations are sampled on.
+const CONTOUR_CELL: f32 = 16.0;
+/// How many times cells containing part of a relation are subdivided.
+const CONTOUR_DEPTH: u32 = 3;
+/// How many turns non-periodic polar curves like spirals are drawn for.
+const SPIRAL_TURNS: f32 = 6.0;
+/// How close in pixels to an axis a drag has to start to stretch it instead of panning.
+const AXIS_GRAB: f32 = 6.0;
+
+/// The range of `t` or `θ` a parametric or polar curve is drawn over, as typed into the sidebar.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Domain {
+    pub min: String,
+    pub max: String,
+}
+
+impl Default for Domain {
+    fn default() -> Self {
+        Self {
+            min: "0".to_string(),
+            max: "1".to_string(),
+        }
+    }
+}
+
+impl Domain {
+    pub fn polar() -> Self {
+        Self {
+            min: "0".to_string(),
+            max: "2\\pi".to_string(),
+        }
+    }
+
+    pub fn range(&self) -> Option<(f32, f32)> {
+        Some((parse_bound(&self.min)?, parse_bound(&self.max)?))
+    }
+
+    /// Like [`Domain::range`], with an error to show when a bound doesn't parse.
+    pub fn bounds(&self) -> Result<(f32, f32)> {
+        self.range().ok_or_else(|| anyhow!("invalid domain"))
+    }
+}
+
+/// Parses a number optionally followed by `\pi`, like `-2\pi` or `0.5`.
+pub fn parse_bound(s: &str) -> Option<f32> {
+    let s = s.trim();
+    let Some(coefficient) = s.strip_suffix("\\pi").or_else(|| s.strip_suffix('π')) else {
+        return s.parse().ok();
+    };
+    let coefficient = match coefficient.trim() {
+        "" => 1.0,
+        "-" => -1.0,
+        c => c.parse().ok()?,
+    };
+    Some(coefficient * std::f32::consts::PI)
+}
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlotKind {
+    /// `y = f(x)`
+    Function,
+    /// `f(x, y) = 0`
+    Relation,
+    /// `(f(t), g(t))`
+    Parametric,
+    /// `r = f(θ)`
+    Polar,
+}
+
+impl PlotKind {
+    /// The name of the variable swept over the expression's [`Domain`], if it has one.
+    pub fn parameter(self) -> Option<&'static str> {
+        match self {
+            PlotKind::Parametric => Some("t"),
+            PlotKind::Polar => Some("θ"),
+            PlotKind::Function | PlotKind::Relation => None,
+        }
+    }
+}
+
+/// Rewrites `r = f(\theta)` into `f(x)`, which is compiled like a function and then drawn with
+/// `θ` bound in place of `x`.
+pub fn polar_body(latex: &str) -> Option<String> {
+    let body = latex
+        .trim()
+        .strip_prefix('r')?
+        .trim_start()
+        .strip_prefix('=')?;
+    Some(
+        body.replace("\\theta", "\\left(x\\right)")
+            .replace('θ', "\\left(x\\right)"),
+    )
+}
+
+/// How an expression's latex was changed before being handed to desmoxide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rewrite {
+    Polar,
+    Inequality(Inequality),
+}
+
+pub fn rewrite(latex: &str) -> Option<(String, Rewrite)> {
+    if let Some(body) = polar_body(latex) {
+        return Some((body, Rewrite::Polar));
+    }
+    let (body, inequality) = inequality::parse(latex)?;
+    Some((body, Rewrite::Inequality(inequality)))
+}
+
+/// The region of the plane a graph shows, as stored in the `graph.viewport` of a graph state.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub xmin: f32,
+    pub ymin: f32,
+    pub xmax: f32,
+    pub ymax: f32,
+}
+
+impl Viewport {
+    /// The scale and center that show all of the viewport in `size`. With `square_axes`, the
+    /// extra room goes to the axis with more of it when the aspect ratios differ, otherwise the
+    /// viewport is stretched to fill `size`.
+    pub fn fit(&self, size: Size, square_axes: bool, axes: Axes) -> Option<(Vector, Vector)> {
+        let min = axes.forward(Vector::new(self.xmin, self.ymin));
+        let max = axes.forward(Vector::new(self.xmax, self.ymax));
+        let (width, height) = (max.x - min.x, max.y - min.y);
+        if !(width > 0.0 && height > 0.0) || size.width <= 0.0 || size.height <= 0.0 {
+            return None;
+        }
+        let mut scale = Vector::new(size.width / width, size.height / height);
+        if square_axes {
+            scale.x = scale.x.min(scale.y);
+            scale.y = scale.x;
+        }
+        let mid = Vector::new(min.x + width / 2.0, min.y + height / 2.0);
+        Some((scale, mid))
+    }
+
+    /// The region shown at `scale` around `mid` in a pane of `size`.
+    pub fn visible(scale: Vector, mid: Vector, size: Size, axes: Axes) -> Option<Self> {
+        if size.width <= 0.0 || size.height <= 0.0 {
+            return None;
+        }
+        let half = Vector::new(size.width / scale.x, size.height / scale.y) * 0.5;
+        let (min, max) = (axes.inverse(mid - half), axes.inverse(mid + half));
+        Some(Self {
+            xmin: min.x,
+            ymin: min.y,
+            xmax: max.x,
+            ymax: max.y,
+        })
+    }
+
+    /// Moves bounds that a logarithmic axis can't show onto its positive side.
+    pub fn clamp_to(self, axes: Axes) -> Self {
+        let clamp = |scale: AxisScale, min: f32, max: f32| match scale {
+            AxisScale::Log if min <= 0.0 => {
+                let max = if max > 0.0 { max } else { 10.0 };
+                (max / 1000.0, max)
+            }
+            _ => (min, max),
+        };
+        let (xmin, xmax) = clamp(axes.x, self.xmin, self.xmax);
+        let (ymin, ymax) = clamp(axes.y, self.ymin, self.ymax);
+        Self {
+            xmin,
+            ymin,
+            xmax,
+            ymax,
+        }
+    }
+}
+
+/// How the graph paper is drawn, as set in the settings pane.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphSettings {
+    pub axes: Axes,
+    pub show_grid: bool,
+    pub show_x_axis: bool,
+    pub show_y_axis: bool,
+    /// Whether each axis is numbered.
+    pub show_x_labels: bool,
+    pub show_y_labels: bool,
+    pub x_title: String,
+    pub y_title: String,
+}
+
+impl Default for GraphSettings {
+    fn default() -> Self {
+        Self {
+            axes: Axes::default(),
+            show_grid: true,
+            show_x_axis: true,
+            show_y_axis: true,
+            show_x_labels: true,
+            show_y_labels: true,
+            x_title: String::new(),
+            y_title: String::new(),
+        }
+    }
+}
+
+/// Where the graph pane is looking and what it has worked out about what it shows.
+pub struct GraphView {
+    /// Pixels per unit along each axis.
+    pub scale: Vector,
+    pub mid: Vector,
+    /// The size of the graph pane, which isn't known until it is first drawn.
+    pub size: Size,
+    /// The points each expression was last drawn with, kept around for tracing.
+    pub samples: RefCell<HashMap<ExpressionId, Vec<Option<Vector>>>>,
+    pub analysis: RefCell<Analysis>,
+    pub hover: Option<Trace>,
+    /// A traced point that stays shown until it is clicked again.
+    pub pinned: Option<Trace>,
+    pub point_budget: u32,
+}
+
+impl Default for GraphView {
+    fn default() -> Self {
+        Self {
+            scale: Vector::new(100.0, 100.0),
+            mid: Vector { x: 0.0, y: 0.0 },
+            size: Size::ZERO,
+            samples: RefCell::default(),
+            analysis: RefCell::default(),
+            hover: None,
+            pinned: None,
+            point_budget: 4000,
+        }
+    }
+}
+
+pub struct GraphRenderer<'a> {
     exprs: &'a CompiledEquations,
     graph_caches: &'a HashMap<ExpressionId, Cache>,
+    order: &'a [ExpressionId],
+    state: &'a ExpressionState,
+    settings: &'a GraphSettings,
+    view: &'a GraphView,
 }
 
 impl<'a> GraphRenderer<'a> {
     pub fn new(
         exprs: &'a CompiledEquations,
         graph_caches: &'a HashMap<ExpressionId, Cache>,
-        scale: f32,
-        mid: Vector,
-        resolution: u32,
+        order: &'a [ExpressionId],
+        state: &'a ExpressionState,
+        settings: &'a GraphSettings,
+        view: &'a GraphView,
     ) -> Self {
         Self {
             exprs,
             graph_caches,
-            scale,
-            mid,
-            resolution,
+            order,
+            state,
+            settings,
+            view,
+        }
+    }
+}
+
+impl<'a> GraphRenderer<'a> {
+    fn plot(&self, id: ExpressionId, ast: &IRSegment, size: Size) -> Result<Vec<Option<Vector>>> {
+        let Some(kind) = self.state.kinds.get(&id) else {
+            return Ok(Vec::new());
+        };
+        let axes = self.settings.axes;
+        match kind {
+            PlotKind::Relation => {
+                let half = Vector::new(
+                    size.width / self.view.scale.x,
+                    size.height / self.view.scale.y,
+                ) * 0.5;
+                contour::contour(
+                    ast,
+                    axes,
+                    self.view.mid - half,
+                    self.view.mid + half,
+                    (size.width / CONTOUR_CELL).ceil() as u32,
+                    (size.height / CONTOUR_CELL).ceil() as u32,
+                    CONTOUR_DEPTH,
+                )
+            }
+            PlotKind::Function => points(
+                ast,
+                size.width / self.view.scale.x,
+                self.view.mid,
+                self.view.scale,
+                axes,
+                self.view.point_budget,
+            ),
+            PlotKind::Parametric => {
+                let domain = self.state.domains.get(&id).cloned().unwrap_or_default();
+                let (min, max) = domain.bounds()?;
+                sampling::sample(
+                    |t| Ok(axes.forward(eval_parametric(ast, t as f64)?)),
+                    min,
+                    max,
+                    self.view.scale,
+                    self.view.point_budget,
+                )
+            }
+            PlotKind::Polar => {
+                let domain = self
+                    .state
+                    .domains
+                    .get(&id)
+                    .cloned()
+                    .unwrap_or_else(Domain::polar);
+                let (min, mut max) = domain.bounds()?;
+                // Like Desmos, keep going around when the default domain doesn't close the curve.
+                if domain == Domain::polar() && !is_periodic(ast)? {
+                    max = SPIRAL_TURNS * std::f32::consts::TAU;
+                }
+                sampling::sample(
+                    |theta| Ok(axes.forward(eval_polar(ast, theta)?)),
+                    min,
+                    max,
+                    self.view.scale,
+                    self.view.point_budget,
+                )
+            }
+        }
+    }
+
+    fn draw_equation(&self, frame: &mut Frame, id: ExpressionId, ast: &IRSegment) -> Result<()> {
+        let points = self.plot(id, ast, frame.size())?;
+        let style = self.state.styles.get(&id).copied().unwrap_or_default();
+        let mut stroke = style.stroke();
+
+        if let Some(comparison) = self.state.inequalities.get(&id) {
+            let fill = style.fill();
+            match self.state.kinds.get(&id) {
+                Some(PlotKind::Function) => inequality::fill_function(
+                    frame,
+                    &points,
+                    *comparison,
+                    self.view.mid,
+                    self.view.scale,
+                    fill,
+                ),
+                Some(PlotKind::Relation) => inequality::fill_relation(
+                    frame,
+                    ast,
+                    *comparison,
+                    self.settings.axes,
+                    self.view.mid,
+                    self.view.scale,
+                    fill,
+                )?,
+                _ => (),
+            }
+            if comparison.is_strict() {
+                stroke = Style {
+                    line_style: LineStyle::Dashed,
+                    ..style
+                }
+                .stroke();
+            }
+        }
+
+        self.draw_points(frame, &points, stroke);
+        self.analyse(id, ast, &points, frame.size());
+        self.view.samples.borrow_mut().insert(id, points);
+        Ok(())
+    }
+
+    /// Finds the points of interest of a curve `y = f(x)` in view, and where it crosses the other
+    /// ones. Crossings already found for a pair of curves are kept until one of them changes.
+    fn analyse(&self, id: ExpressionId, ast: &IRSegment, points: &[Option<Vector>], size: Size) {
+        let is_function = |i: &ExpressionId| {
+            self.state.kinds.get(i) == Some(&PlotKind::Function)
+                && !self.state.inequalities.contains_key(i)
+        };
+        let mut results = self.view.analysis.borrow_mut();
+        if !is_function(&id) {
+            results.remove(id);
+            return;
+        }
+
+        let axes = self.settings.axes;
+        let half = size.width / self.view.scale.x / 2.0;
+        let (min, max) = (self.view.mid.x - half, self.view.mid.x + half);
+        results.set_points(
+            id,
+            analysis::points_of_interest(ast, points, axes, min, max),
+        );
+        // Crossings are looked for a view's width either side, so panning a little doesn't need
+        // them again.
+        let (from, to) = (min - (max - min), max + (max - min));
+        for (other, eq) in &self.exprs.compiled_equations {
+            if *other == id
+                || !is_function(other)
+                || results.has_intersections(id, *other, min, max)
+            {
+                continue;
+            }
+            let found = analysis::intersections(ast, segment(eq), axes, from, to);
+            results.set_intersections(id, *other, from, to, found);
+        }
+    }
+
+    fn shown(&self) -> impl Iterator<Item = &ExpressionId> {
+        self.order
+            .iter()
+            .filter(|i| !self.state.hidden.contains(*i))
+    }
+
+    /// The curve point under `cursor` to trace, if any.
+    fn nearest(&self, cursor: Point, size: Size) -> Option<Trace> {
+        let samples = self.view.samples.borrow();
+        let axes = self.settings.axes;
+        let candidates = self
+            .shown()
+            .filter_map(|i| Some((*i, samples.get(i)?)))
+            .flat_map(|(i, points)| points.iter().flatten().map(move |p| (i, axes.inverse(*p))));
+        trace::nearest(
+            candidates,
+            cursor,
+            self.view.mid,
+            self.view.scale,
+            size,
+            axes,
+        )
+    }
+
+    /// The root, extremum, intercept or intersection under `cursor`, if any.
+    fn nearest_point_of_interest(&self, cursor: Point, size: Size) -> Option<Trace> {
+        let analysis = self.view.analysis.borrow();
+        let candidates = analysis.shown(|i| !self.state.hidden.contains(&i));
+        trace::nearest(
+            candidates,
+            cursor,
+            self.view.mid,
+            self.view.scale,
+            size,
+            self.settings.axes,
+        )
+    }
+
+    fn draw_points(&self, frame: &mut Frame, points: &[Option<Vector>], stroke: Stroke) {
+        let size = frame.size();
+        let curve = Path::new(|builder| {
+            let mut pen_down = false;
+            for point in points {
+                match point.map(|p| translate_point(p, self.view.mid, self.view.scale, size)) {
+                    Some(p) if p.y.is_finite() && p.y < size.height * 2.0 && p.y > -size.height => {
+                        if pen_down {
+                            builder.line_to(p);
+                        } else {
+                            builder.move_to(p);
+                        }
+                        pen_down = true;
+                    }
+                    _ => pen_down = false,
+                }
+            }
+        });
+        frame.stroke(&curve, stroke);
+    }
+}
+
+pub fn segment(graph: &CompiledEquation) -> &IRSegment {
+    let (CompiledEquation::Implicit { lhs } | CompiledEquation::Explicit { lhs, .. }) = graph;
+    lhs
+}
+
+pub fn eval_value(ast: &IRSegment, x: f64, y: f64) -> Result<IRValue> {
+    eval(
+        ast,
+        vec![IRValue::Number(x.into()), IRValue::Number(y.into())],
+    )
+}
+
+pub fn eval_point(ast: &IRSegment, x: f64, y: f64) -> Result<f32> {
+    match eval_value(ast, x, y)? {
+        IRValue::Number(v) => Ok(v.into()),
+        _ => Err(anyhow!("expected number return")),
+    }
+}
+
+/// Evaluates `ast` at a point given in the coordinates the view is linear in.
+pub fn eval_view(ast: &IRSegment, axes: Axes, p: Vector) -> Result<f32> {
+    let p = axes.inverse(p);
+    eval_point(ast, p.x as f64, p.y as f64)
+}
+
+/// Evaluates a curve returning a point, binding `t` in place of `x`.
+pub fn eval_parametric(ast: &IRSegment, t: f64) -> Result<Vector> {
+    match eval_value(ast, t, 0.0)? {
+        IRValue::Vec2(x, y) => Ok(Vector::new(x.into(), y.into())),
+        _ => Err(anyhow!("expected point return")),
+    }
+}
+
+pub fn eval_polar(ast: &IRSegment, theta: f32) -> Result<Vector> {
+    let r = eval_point(ast, theta as f64, 0.0)?;
+    Ok(Vector::new(r * theta.cos(), r * theta.sin()))
+}
+
+fn is_periodic(ast: &IRSegment) -> Result<bool> {
+    for theta in [0.3, 1.7, 4.1] {
+        let a = eval_point(ast, theta, 0.0)?;
+        let b = eval_point(ast, theta + std::f64::consts::TAU, 0.0)?;
+        if (a - b).abs() > 1e-4 * a.abs().max(1.0) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+pub fn plot_kind(ast: &IRSegment) -> Result<PlotKind> {
+    match eval_value(ast, 0.0, 0.0)? {
+        IRValue::Vec2(..) => Ok(PlotKind::Parametric),
+        IRValue::Number(_) if is_relation(ast)? => Ok(PlotKind::Relation),
+        IRValue::Number(_) => Ok(PlotKind::Function),
+        _ => Err(anyhow!("expected number or point return")),
+    }
+}
+
+/// Whether `ast` depends on `y`, in which case it is drawn as the relation `ast(x, y) = 0`
+/// instead of the curve `y = ast(x)`.
+fn is_relation(ast: &IRSegment) -> Result<bool> {
+    for (x, y) in [(0.3, 0.7), (-1.9, 2.3), (4.1, -3.7)] {
+        let a = eval_point(ast, x, 0.0)?;
+        let b = eval_point(ast, x, y)?;
+        if a != b && !(a.is_nan() && b.is_nan()) {
+            return Ok(true);
         }
     }
+    Ok(false)
 }
 
 pub fn points(
     ast: &IRSegment,
     range: f32,
     mid: Vector,
-    resolution: u32,
+    scale: Vector,
+    axes: Axes,
+    point_budget: u32,
 ) -> Result<Vec<Option<Vector>>> {
-    let mut points = Vec::new();
-
-    let min = mid.x - range / 2.0;
-    let dx = range / resolution as f32;
-    let mut args = Vec::new();
-    args.push(IRValue::Number(0.0.into()));
-
-    args.push(IRValue::Number(0.0.into()));
-    for i in 0..resolution {
-        let x = i as f64 * dx as f64 + min as f64;
-        args[0] = IRValue::Number(x.into());
-        let point = Some(Vector {
-            x: x as f32,
-            y: match eval(ast, args.clone())? {
-                IRValue::Number(y) => y.into(),
-
-                _ => return Err(anyhow!("expected number return")),
-            },
-        });
-        points.push(point);
-    }
+    sampling::sample(
+        |u| {
+            let x = axes.x.inverse(u);
+            Ok(Vector::new(
+                u,
+                axes.y.forward(eval_point(ast, x as f64, 0.0)?),
+            ))
+        },
+        mid.x - range / 2.0,
+        mid.x + range / 2.0,
+        scale,
+        point_budget,
+    )
+}
 
-    Ok(points)
+#[derive(Default)]
+pub struct GraphState {
+    drag: Drag,
+    modifiers: keyboard::Modifiers,
 }
 
-pub enum GraphState {
+#[derive(Default)]
+enum Drag {
+    #[default]
     None,
-    Moving { start: Point },
+    Moving {
+        start: Point,
+    },
+    /// Dragging along an axis stretches it, keeping the origin in place.
+    Stretching {
+        axis: Axis,
+        start: Point,
+    },
 }
 
-impl Default for GraphState {
-    fn default() -> Self {
-        Self::None
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Axis {
+    X,
+    Y,
 }
 
-pub fn translate_point(point: Vector, mid: Vector, scale: f32, size: Size) -> Point {
+pub fn translate_point(point: Vector, mid: Vector, scale: Vector, size: Size) -> Point {
     Point {
-        x: translate_coord(point.x, mid.x, scale, size.width),
-        y: translate_coord(point.y, mid.y, -scale, size.height),
+        x: translate_coord(point.x, mid.x, scale.x, size.width),
+        y: translate_coord(point.y, mid.y, -scale.y, size.height),
     }
 }
 
+/// The point of the graph drawn at `point` on the screen.
+pub fn untranslate_point(point: Point, mid: Vector, scale: Vector, size: Size) -> Vector {
+    Vector::new(
+        (point.x - size.width / 2.0) / scale.x + mid.x,
+        (size.height / 2.0 - point.y) / scale.y + mid.y,
+    )
+}
+
 pub fn translate_coord(point: f32, mid: f32, scale: f32, size: f32) -> f32 {
     (point - mid) * scale + size / 2.0
 }
@@ -98,94 +637,78 @@ impl<'a> Program<Message> for GraphRenderer<'a> {
     type State = GraphState;
     fn draw(
         &self,
-        _: &Self::State,
+        state: &Self::State,
         renderer: &iced::Renderer,
         _: &Theme,
         bounds: iced::Rectangle,
         _: Cursor,
     ) -> Vec<Geometry> {
-        let graphs = self.exprs.compiled_equations.iter().map(|(i, graph)| {
-            self.graph_caches[i].draw(renderer, bounds.size(), |frame| match graph {
-                CompiledEquation::Implicit { lhs } => {
-                    match points(lhs, bounds.width / self.scale, self.mid, self.resolution) {
-                        Ok(points) => {
-                            for i in 0..points.len() - 1 {
-                                match (points[i], points[i + 1]) {
-                                    (Some(point), Some(next_point)) => {
-                                        let point = translate_point(
-                                            point,
-                                            self.mid,
-                                            self.scale,
-                                            bounds.size(),
-                                        );
-
-                                        let next_point = translate_point(
-                                            next_point,
-                                            self.mid,
-                                            self.scale,
-                                            bounds.size(),
-                                        );
-
-                                        if point.y > frame.size().height * 2.0
-                                            || point.y < -frame.size().height
-                                            || point.y.is_nan()
-                                            || !point.y.is_finite()
-                                            || !next_point.y.is_finite()
-                                            || !next_point.y.is_finite()
-                                        {
-                                            continue;
-                                        }
-
-                                        frame.stroke(
-                                            &Path::line(point, next_point),
-                                            Stroke::default()
-                                                .with_width(3.0)
-                                                .with_color(Color::from_rgb8(45, 112, 179)),
-                                        );
-                                    }
-                                    _ => (),
-                                }
-                            }
-                        }
-                        Err(e) => eprintln!("error in eval, {}", e),
-                    }
-                }
-                CompiledEquation::Explicit { .. } => (),
+        // Expressions are drawn in sidebar order, so later ones end up on top. Hidden expressions
+        // keep their cache, so showing them again doesn't redraw them.
+        let shown = self
+            .shown()
+            .filter_map(|i| Some((i, self.exprs.compiled_equations.get(i)?)));
+        let graphs = shown.map(|(i, graph)| {
+            self.graph_caches[i].draw(renderer, bounds.size(), |frame| {
+                // A point that fails to evaluate stops the whole curve, so the error is shown next
+                // to the expression instead.
+                let result = self.draw_equation(frame, *i, segment(graph));
+                let mut errors = self.state.draw_errors.borrow_mut();
+                match result {
+                    Ok(()) => errors.remove(i),
+                    Err(e) => errors.insert(*i, e.to_string()),
+                };
             })
         });
 
-        let mut graphs: Vec<_> = graphs.collect();
-
         let mut grid = Frame::new(renderer, bounds.size());
-        grid.stroke(
-            &Path::line(
-                Point::new(
-                    translate_coord(0.0, self.mid.x, self.scale, bounds.size().width),
-                    bounds.size().height,
-                ),
-                Point::new(
-                    translate_coord(0.0, self.mid.x, self.scale, bounds.size().width),
-                    0.0,
-                ),
-            ),
-            Stroke::default().with_width(3.0),
-        );
+        grid::draw(&mut grid, self.view.mid, self.view.scale, self.settings);
 
-        grid.stroke(
-            &Path::line(
-                Point::new(
-                    bounds.size().width,
-                    translate_coord(0.0, self.mid.y, -self.scale, bounds.size().height),
-                ),
-                Point::new(
-                    0.0,
-                    translate_coord(0.0, self.mid.y, -self.scale, bounds.size().height),
-                ),
-            ),
-            Stroke::default().with_width(3.0),
-        );
-        graphs.push(grid.into_geometry());
-        graphs
+        let mut geometry = vec![grid.into_geometry()];
+        geometry.extend(graphs);
+
+        // Points of interest go under traced points. The hovered point is hidden while dragging,
+        // pinned points stay where they are.
+        let mut markers = Frame::new(renderer, bounds.size());
+        for (_, point) in self
+            .view
+            .analysis
+            .borrow()
+            .shown(|i| !self.state.hidden.contains(&i))
+        {
+            trace::draw_point_of_interest(
+                &mut markers,
+                point,
+                self.view.mid,
+                self.view.scale,
+                self.settings.axes,
+            );
+        }
+        let hover = self
+            .view
+            .hover
+            .filter(|hover| matches!(state.drag, Drag::None) && Some(*hover) != self.view.pinned);
+        for trace in self.view.pinned.into_iter().chain(hover) {
+            if self.state.hidden.contains(&trace.id) {
+                continue;
+            }
+            let style = self
+                .state
+                .styles
+                .get(&trace.id)
+                .copied()
+                .unwrap_or_default();
+            trace::draw(
+                &mut markers,
+                trace,
+                style.color,
+                self.view.mid,
+                self.view.scale,
+                self.settings.axes,
+            );
+        }
+        geometry.push(markers.into_geometry());
+        geometry
     }
 
     fn update(
@@ -195,55 +718,173 @@ impl<'a> Program<Message> for GraphRenderer<'a> {
         bounds: iced::Rectangle,
         cursor: mouse::Cursor,
     ) -> (Status, Option<Message>) {
+        let (status, message) = self.handle(state, event, bounds, cursor);
+        // Only the canvas knows how big the pane is. A new size is reported with the first
+        // event that has nothing else to report, so no event is lost to it.
+        match message {
+            None if bounds.size() != self.view.size => {
+                (status, Some(Message::GraphResized(bounds.size())))
+            }
+            message => (status, message),
+        }
+    }
+}
+
+impl<'a> GraphRenderer<'a> {
+    /// Turns a canvas event into what it does to the graph.
+    fn handle(
+        &self,
+        state: &mut GraphState,
+        event: event::Event,
+        bounds: iced::Rectangle,
+        cursor: mouse::Cursor,
+    ) -> (Status, Option<Message>) {
+        if let Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) = event {
+            state.modifiers = modifiers;
+            return (event::Status::Ignored, None);
+        }
         let Some(cursor_position) = cursor.position_in(bounds) else {
             return (event::Status::Ignored, None);
         };
+        let origin = translate_point(
+            Vector::new(0.0, 0.0),
+            self.view.mid,
+            self.view.scale,
+            bounds.size(),
+        );
         match event {
             Event::Mouse(event) => match event {
                 mouse::Event::ButtonPressed(mouse::Button::Left) => {
-                    match *state {
-                        GraphState::None => {
-                            *state = GraphState::Moving {
-                                start: cursor_position,
-                            };
-                        }
-                        _ => {}
+                    if let Drag::None = state.drag {
+                        // Only drawn axes can be grabbed, and log axes have no zero to draw the
+                        // other axis through.
+                        let axes = self.settings.axes;
+                        let near_x = self.settings.show_x_axis
+                            && axes.y == AxisScale::Linear
+                            && (cursor_position.y - origin.y).abs() < AXIS_GRAB;
+                        let near_y = self.settings.show_y_axis
+                            && axes.x == AxisScale::Linear
+                            && (cursor_position.x - origin.x).abs() < AXIS_GRAB;
+                        let start = cursor_position;
+                        state.drag = match (near_x, near_y) {
+                            (true, false) => Drag::Stretching {
+                                axis: Axis::X,
+                                start,
+                            },
+                            (false, true) => Drag::Stretching {
+                                axis: Axis::Y,
+                                start,
+                            },
+                            _ => Drag::Moving { start },
+                        };
                     }
-                    (event::Status::Captured, None)
+                    if !matches!(state.drag, Drag::Moving { .. }) {
+                        return (event::Status::Captured, None);
+                    }
+
+                    // Clicking a curve pins the point under the cursor, and clicking the pinned
+                    // point again unpins it. Either way the click can go on to pan.
+                    let size = bounds.size();
+                    let axes = self.settings.axes;
+                    let pin = match self.view.pinned {
+                        Some(pinned)
+                            if trace::hits(
+                                pinned,
+                                cursor_position,
+                                self.view.mid,
+                                self.view.scale,
+                                size,
+                                axes,
+                            ) =>
+                        {
+                            Some(Message::Pin(None))
+                        }
+                        _ => self
+                            .nearest_point_of_interest(cursor_position, size)
+                            .or_else(|| self.nearest(cursor_position, size))
+                            .map(|trace| Message::Pin(Some(trace))),
+                    };
+                    (event::Status::Captured, pin)
                 }
                 mouse::Event::ButtonReleased(mouse::Button::Left) => {
-                    *state = GraphState::None;
+                    state.drag = Drag::None;
                     (event::Status::Captured, None)
                 }
-                mouse::Event::CursorMoved { .. } => match *state {
-                    GraphState::Moving { start } => {
-                        let mut diff = (start - cursor_position) * (1.0 / self.scale);
-                        diff.y *= -1.0;
-                        *state = GraphState::Moving {
+                mouse::Event::CursorMoved { .. } => match state.drag {
+                    Drag::Moving { start } => {
+                        let diff = start - cursor_position;
+                        state.drag = Drag::Moving {
                             start: cursor_position,
                         };
-                        (event::Status::Captured, Some(Message::Moved(diff)))
+                        (
+                            event::Status::Captured,
+                            Some(Message::Moved(Vector::new(
+                                diff.x / self.view.scale.x,
+                                -diff.y / self.view.scale.y,
+                            ))),
+                        )
+                    }
+                    Drag::Stretching { axis, start } => {
+                        let ratio = match axis {
+                            Axis::X => (cursor_position.x - origin.x) / (start.x - origin.x),
+                            Axis::Y => (cursor_position.y - origin.y) / (start.y - origin.y),
+                        };
+                        // Past the origin the axis would flip, and right next to it the ratio blows up.
+                        if !(ratio.is_finite() && ratio > 0.0) {
+                            return (event::Status::Captured, None);
+                        }
+                        state.drag = Drag::Stretching {
+                            axis,
+                            start: cursor_position,
+                        };
+                        let (scale, mid) = match axis {
+                            Axis::X => (
+                                Vector::new(self.view.scale.x * ratio, self.view.scale.y),
+                                Vector::new(self.view.mid.x / ratio, self.view.mid.y),
+                            ),
+                            Axis::Y => (
+                                Vector::new(self.view.scale.x, self.view.scale.y * ratio),
+                                Vector::new(self.view.mid.x, self.view.mid.y / ratio),
+                            ),
+                        };
+                        (
+                            event::Status::Captured,
+                            Some(Message::Scaled(scale, Some(mid))),
+                        )
+                    }
+                    Drag::None => {
+                        let hover = self.nearest(cursor_position, bounds.size());
+                        if hover == self.view.hover {
+                            (event::Status::Ignored, None)
+                        } else {
+                            (event::Status::Ignored, Some(Message::Hover(hover)))
+                        }
                     }
-                    GraphState::None => (event::Status::Ignored, None),
                 },
                 mouse::Event::WheelScrolled { delta } => match delta {
                     mouse::ScrollDelta::Lines { y, .. } | mouse::ScrollDelta::Pixels { y, .. } => {
-                        let scaling = self.scale * (1.0 + y / 30.0);
-                        let mid = if let Some(cursor_to_center) =
-                            cursor.position_from(bounds.center())
-                        {
-                            let factor = scaling - self.scale;
-
-                            Some(
-                                self.mid
-                                    - Vector::new(
-                                        -cursor_to_center.x * factor / (self.scale * self.scale),
-                                        cursor_to_center.y * factor / (self.scale * self.scale),
-                                    ),
-                            )
-                        } else {
-                            None
-                        };
+                        let factor = 1.0 + y / 30.0;
+                        // Shift zooms only the x axis, and ctrl only the y axis.
+                        let (factor_x, factor_y) =
+                            match (state.modifiers.shift(), state.modifiers.control()) {
+                                (true, false) => (factor, 1.0),
+                                (false, true) => (1.0, factor),
+                                _ => (factor, factor),
+                            };
+                        let scaling =
+                            Vector::new(self.view.scale.x * factor_x, self.view.scale.y * factor_y);
+                        // Keep the point under the cursor where it is.
+                        let mid = cursor
+                            .position_from(bounds.center())
+                            .map(|cursor_to_center| {
+                                self.view.mid
+                                    + Vector::new(
+                                        cursor_to_center.x
+                                            * (1.0 / self.view.scale.x - 1.0 / scaling.x),
+                                        -cursor_to_center.y
+                                            * (1.0 / self.view.scale.y - 1.0 / scaling.y),
+                                    )
+                            });
                         (event::Status::Captured, Some(Message::Scaled(scaling, mid)))
                     }
                 },
@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+
+use desmoxide::{graph::expressions::ExpressionId, lang::compiler::ir::IRSegment};
+use iced::Vector;
+
+use super::{eval_point, Axes, AxisScale};
+
+/// Number of evenly spaced samples two curves are compared at to find where they cross.
+const INTERSECTION_SAMPLES: u32 = 512;
+/// Iterations Brent's method gets to converge.
+const MAX_ITERATIONS: u32 = 100;
+/// Step used for numeric derivatives, relative to the width of the bracket.
+const DERIVATIVE_STEP: f64 = 1e-4;
+/// How close two points have to be to count as the same one, relative to the width of the view.
+const DUPLICATE_DISTANCE: f32 = 1e-4;
+/// How small a refined root has to be compared to the ends of its bracket. A sign change that
+/// doesn't shrink is a jump across zero, not a root.
+const ROOT_TOLERANCE: f64 = 1e-3;
+
+/// Roots, extrema, intercepts and intersections of the curves last drawn, in graph coordinates.
+/// Only curves `y = f(x)` are analysed.
+#[derive(Debug, Default)]
+pub struct Analysis {
+    points: HashMap<ExpressionId, Vec<Vector>>,
+    /// Keyed by the pair of curves, lower id first.
+    intersections: HashMap<(ExpressionId, ExpressionId), Crossings>,
+}
+
+/// Where two curves cross between `min` and `max`, in the view's coordinates. These are kept
+/// until either curve changes, as long as the view stays in that range.
+#[derive(Debug)]
+struct Crossings {
+    min: f32,
+    max: f32,
+    points: Vec<Vector>,
+}
+
+impl Analysis {
+    pub fn set_points(&mut self, id: ExpressionId, points: Vec<Vector>) {
+        self.points.insert(id, points);
+    }
+
+    /// Whether the crossings of `a` and `b` between `min` and `max` are already known.
+    pub fn has_intersections(&self, a: ExpressionId, b: ExpressionId, min: f32, max: f32) -> bool {
+        self.intersections
+            .get(&pair(a, b))
+            .is_some_and(|crossings| crossings.min <= min && max <= crossings.max)
+    }
+
+    pub fn set_intersections(
+        &mut self,
+        a: ExpressionId,
+        b: ExpressionId,
+        min: f32,
+        max: f32,
+        points: Vec<Vector>,
+    ) {
+        self.intersections
+            .insert(pair(a, b), Crossings { min, max, points });
+    }
+
+    /// Forgets everything found on `id`.
+    pub fn remove(&mut self, id: ExpressionId) {
+        self.points.remove(&id);
+        self.intersections.retain(|(a, b), _| *a != id && *b != id);
+    }
+
+    pub fn clear(&mut self) {
+        self.points.clear();
+        self.intersections.clear();
+    }
+
+    /// The points found on curves `is_shown` accepts, with the curve each one is on. Intersections
+    /// are listed under the first of the two curves.
+    pub fn shown<'a>(
+        &'a self,
+        is_shown: impl Fn(ExpressionId) -> bool + 'a,
+    ) -> impl Iterator<Item = (ExpressionId, Vector)> + 'a {
+        let points = self
+            .points
+            .iter()
+            .filter(|(id, _)| is_shown(**id))
+            .flat_map(|(id, points)| points.iter().map(move |p| (*id, *p)));
+        let intersections = self
+            .intersections
+            .iter()
+            .filter(move |((a, b), _)| is_shown(*a) && is_shown(*b))
+            .flat_map(|((a, _), crossings)| crossings.points.iter().map(move |p| (*a, *p)));
+        points.chain(intersections)
+    }
+}
+
+fn pair(a: ExpressionId, b: ExpressionId) -> (ExpressionId, ExpressionId) {
+    if a.0 <= b.0 {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+fn eval(ast: &IRSegment, x: f64) -> f64 {
+    eval_point(ast, x, 0.0).map_or(f64::NAN, |y| y as f64)
+}
+
+/// The roots and local extrema of `y = f(x)`, bracketed by the `samples` it was drawn with, and
+/// its y-intercept if `x = 0` is between `min` and `max`. `samples`, `min` and `max` are in the
+/// view's coordinates.
+pub fn points_of_interest(
+    ast: &IRSegment,
+    samples: &[Option<Vector>],
+    axes: Axes,
+    min: f32,
+    max: f32,
+) -> Vec<Vector> {
+    let f = |x: f64| eval(ast, x);
+    let mut found = Vec::new();
+
+    for run in samples.split(Option::is_none) {
+        let run: Vec<Vector> = run.iter().flatten().map(|p| axes.inverse(*p)).collect();
+        // A curve along the x axis has no single point to mark.
+        if run.iter().all(|p| p.y == 0.0) {
+            continue;
+        }
+
+        for (i, segment) in run.windows(2).enumerate() {
+            let (a, b) = (segment[0], segment[1]);
+            if a.y == 0.0 {
+                // Only mark where a stretch of zeros starts.
+                if i == 0 || run[i - 1].y != 0.0 {
+                    found.push(Vector::new(a.x, 0.0));
+                }
+            } else if b.y != 0.0 && (a.y < 0.0) != (b.y < 0.0) {
+                let root = brent(f, a.x as f64, b.x as f64)
+                    .filter(|x| f(*x).abs() < ROOT_TOLERANCE * a.y.abs().max(b.y.abs()) as f64);
+                if let Some(x) = root {
+                    found.push(Vector::new(x as f32, 0.0));
+                }
+            }
+        }
+
+        // The slope changes sign around a sample that is higher or lower than both neighbours.
+        for window in run.windows(3) {
+            let (a, m, b) = (window[0], window[1], window[2]);
+            if (m.y - a.y) * (b.y - m.y) >= 0.0 {
+                continue;
+            }
+            let h = (b.x - a.x) as f64 * DERIVATIVE_STEP;
+            let slope = |x: f64| (f(x + h) - f(x - h)) / (2.0 * h);
+            if let Some(x) = brent(slope, a.x as f64, b.x as f64) {
+                found.push(Vector::new(x as f32, f(x) as f32));
+            }
+        }
+    }
+
+    if axes.x == AxisScale::Linear && (min..=max).contains(&0.0) {
+        let y = f(0.0);
+        if y.is_finite() {
+            found.push(Vector::new(0.0, y as f32));
+        }
+    }
+    dedup(found, (max - min) * DUPLICATE_DISTANCE, axes)
+}
+
+/// Drops points closer than `distance` to one found before them, like a root at the origin that
+/// is also the y-intercept. `distance` is in the view's coordinates.
+fn dedup(points: Vec<Vector>, distance: f32, axes: Axes) -> Vec<Vector> {
+    let mut kept: Vec<Vector> = Vec::with_capacity(points.len());
+    for point in points {
+        let view = axes.forward(point);
+        let duplicate = kept.iter().any(|k| {
+            let d = axes.forward(*k) - view;
+            d.x.abs() <= distance && d.y.abs() <= distance
+        });
+        if !duplicate {
+            kept.push(point);
+        }
+    }
+    kept
+}
+
+/// Where `y = f(x)` and `y = g(x)` cross between `min` and `max`, given in the view's coordinates.
+pub fn intersections(f: &IRSegment, g: &IRSegment, axes: Axes, min: f32, max: f32) -> Vec<Vector> {
+    let difference = |x: f64| eval(f, x) - eval(g, x);
+    let xs: Vec<f64> = (0..=INTERSECTION_SAMPLES)
+        .map(|i| {
+            let u = min + (max - min) * i as f32 / INTERSECTION_SAMPLES as f32;
+            axes.x.inverse(u) as f64
+        })
+        .collect();
+
+    let mut found = Vec::new();
+    for segment in xs.windows(2) {
+        let (a, b) = (segment[0], segment[1]);
+        let (da, db) = (difference(a), difference(b));
+        // A crossing right at `b` is found by the next segment, which also skips curves that
+        // overlap and have no single point to mark.
+        if db == 0.0 || (da != 0.0 && (da < 0.0) == (db < 0.0)) {
+            continue;
+        }
+        let x = brent(difference, a, b)
+            .filter(|x| difference(*x).abs() <= ROOT_TOLERANCE * da.abs().max(db.abs()));
+        if let Some(x) = x {
+            found.push(Vector::new(x as f32, eval(f, x) as f32));
+        }
+    }
+    found
+}
+
+/// Finds a zero of `f` between `a` and `b` with Brent's method. `f` has to change sign between
+/// them.
+fn brent(f: impl Fn(f64) -> f64, a: f64, b: f64) -> Option<f64> {
+    let (mut a, mut b) = (a, b);
+    let (mut fa, mut fb) = (f(a), f(b));
+    if !(fa.is_finite() && fb.is_finite()) || (fa != 0.0 && fb != 0.0 && (fa < 0.0) == (fb < 0.0)) {
+        return None;
+    }
+    let tolerance = (b - a).abs() * 1e-10;
+
+    let (mut c, mut fc) = (b, fb);
+    let (mut d, mut e) = (b - a, b - a);
+    for _ in 0..MAX_ITERATIONS {
+        if (fb < 0.0) == (fc < 0.0) {
+            (c, fc) = (a, fa);
+            d = b - a;
+            e = d;
+        }
+        if fc.abs() < fb.abs() {
+            (a, fa) = (b, fb);
+            (b, fb) = (c, fc);
+            (c, fc) = (a, fa);
+        }
+
+        let tol = 2.0 * f64::EPSILON * b.abs() + 0.5 * tolerance;
+        let m = 0.5 * (c - b);
+        if m.abs() <= tol || fb == 0.0 {
+            return Some(b);
+        }
+
+        if e.abs() >= tol && fa.abs() > fb.abs() {
+            // Try interpolating, falling back to bisection when it wouldn't converge fast enough.
+            let s = fb / fa;
+            let (mut p, mut q) = if a == c {
+                (2.0 * m * s, 1.0 - s)
+            } else {
+                let q = fa / fc;
+                let r = fb / fc;
+                (
+                    s * (2.0 * m * q * (q - r) - (b - a) * (r - 1.0)),
+                    (q - 1.0) * (r - 1.0) * (s - 1.0),
+                )
+            };
+            if p > 0.0 {
+                q = -q;
+            }
+            p = p.abs();
+            if 2.0 * p < (3.0 * m * q - (tol * q).abs()).min((e * q).abs()) {
+                e = d;
+                d = p / q;
+            } else {
+                d = m;
+                e = d;
+            }
+        } else {
+            d = m;
+            e = d;
+        }
+
+        (a, fa) = (b, fb);
+        b += if d.abs() > tol { d } else { tol.copysign(m) };
+        fb = f(b);
+        if !fb.is_finite() {
+            return None;
+        }
+    }
+    Some(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn brent_finds_known_roots() {
+        let sqrt2 = brent(|x| x * x - 2.0, 0.0, 2.0).unwrap();
+        assert!((sqrt2 - 2f64.sqrt()).abs() < 1e-9, "{sqrt2}");
+        let half_pi = brent(f64::cos, 0.0, 3.0).unwrap();
+        assert!(
+            (half_pi - std::f64::consts::FRAC_PI_2).abs() < 1e-9,
+            "{half_pi}"
+        );
+        // Either end can be the root.
+        assert_eq!(brent(|x| x - 1.0, 1.0, 2.0), Some(1.0));
+    }
+
+    #[test]
+    fn brent_needs_a_sign_change() {
+        assert_eq!(brent(|x| x * x + 1.0, -1.0, 1.0), None);
+        assert_eq!(brent(|x| 1.0 / x, 0.0, 1.0), None);
+    }
+
+    #[test]
+    fn nearby_points_are_deduplicated() {
+        let axes = Axes::default();
+        let points = vec![
+            Vector::new(0.0, 0.0),
+            Vector::new(1e-6, 0.0),
+            Vector::new(1.0, 0.0),
+            Vector::new(1.0, 1.0),
+        ];
+        assert_eq!(
+            dedup(points, 1e-4, axes),
+            vec![
+                Vector::new(0.0, 0.0),
+                Vector::new(1.0, 0.0),
+                Vector::new(1.0, 1.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn intersections_are_kept_per_pair_and_range() {
+        let (a, b, c) = (ExpressionId(1), ExpressionId(2), ExpressionId(3));
+        let mut analysis = Analysis::default();
+        analysis.set_intersections(b, a, -10.0, 10.0, vec![Vector::new(1.0, 1.0)]);
+        assert!(analysis.has_intersections(a, b, -5.0, 5.0));
+        assert!(!analysis.has_intersections(a, b, -5.0, 20.0));
+        assert!(!analysis.has_intersections(a, c, -5.0, 5.0));
+
+        analysis.set_points(c, vec![Vector::new(2.0, 0.0)]);
+        assert_eq!(analysis.shown(|_| true).count(), 2);
+        assert_eq!(analysis.shown(|i| i != b).count(), 1);
+
+        analysis.remove(b);
+        assert!(!analysis.has_intersections(a, b, -5.0, 5.0));
+        assert_eq!(analysis.shown(|_| true).count(), 1);
+    }
+}
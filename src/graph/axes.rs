@@ -0,0 +1,61 @@
+use iced::Vector;
+
+/// How values along an axis are spread out on screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AxisScale {
+    #[default]
+    Linear,
+    /// Powers of ten are evenly spaced, and values that aren't positive aren't shown at all.
+    Log,
+}
+
+impl AxisScale {
+    /// Parses the `xAxisScale` and `yAxisScale` fields of a Desmos graph.
+    pub fn from_desmos(s: &str) -> Option<Self> {
+        match s {
+            "linear" => Some(AxisScale::Linear),
+            "logarithmic" => Some(AxisScale::Log),
+            _ => None,
+        }
+    }
+
+    pub fn to_desmos(self) -> &'static str {
+        match self {
+            AxisScale::Linear => "linear",
+            AxisScale::Log => "logarithmic",
+        }
+    }
+
+    /// Maps a graph coordinate to the coordinate the view is linear in.
+    pub fn forward(self, v: f32) -> f32 {
+        match self {
+            AxisScale::Linear => v,
+            AxisScale::Log => v.log10(),
+        }
+    }
+
+    pub fn inverse(self, v: f32) -> f32 {
+        match self {
+            AxisScale::Linear => v,
+            AxisScale::Log => 10f32.powf(v),
+        }
+    }
+}
+
+/// The scales of both axes. `mid`, `scale` and every point the renderer draws are in the
+/// coordinates [`Axes::forward`] maps to, so panning and zooming stay linear.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Axes {
+    pub x: AxisScale,
+    pub y: AxisScale,
+}
+
+impl Axes {
+    pub fn forward(self, p: Vector) -> Vector {
+        Vector::new(self.x.forward(p.x), self.y.forward(p.y))
+    }
+
+    pub fn inverse(self, p: Vector) -> Vector {
+        Vector::new(self.x.inverse(p.x), self.y.inverse(p.y))
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use desmoxide::lang::compiler::ir::IRSegment;
+use iced::Vector;
+
+use super::{eval_view, Axes};
+
+/// Traces the zero level set of `ast(x, y)` over the rectangle spanned by `min` and `max`, which
+/// are given in the coordinates the view is linear in along `axes`.
+///
+/// The rectangle is sampled on a `columns` x `rows` grid, and every cell the curve passes through
+/// is subdivided `depth` more times before marching squares runs on it. The result is a list of
+/// polylines separated by `None`, in the same shape `points` returns.
+pub fn contour(
+    ast: &IRSegment,
+    axes: Axes,
+    min: Vector,
+    max: Vector,
+    columns: u32,
+    rows: u32,
+    depth: u32,
+) -> Result<Vec<Option<Vector>>> {
+    let dx = (max.x - min.x) / columns as f32;
+    let dy = (max.y - min.y) / rows as f32;
+
+    let mut values = Vec::with_capacity(((columns + 1) * (rows + 1)) as usize);
+    for j in 0..=rows {
+        for i in 0..=columns {
+            values.push(eval_view(
+                ast,
+                axes,
+                Vector::new(min.x + i as f32 * dx, min.y + j as f32 * dy),
+            )?);
+        }
+    }
+    let value = |i: u32, j: u32| values[(j * (columns + 1) + i) as usize];
+
+    let mut segments = Vec::new();
+    for j in 0..rows {
+        for i in 0..columns {
+            let cell = Cell {
+                min: Vector::new(min.x + i as f32 * dx, min.y + j as f32 * dy),
+                size: Vector::new(dx, dy),
+                corners: [
+                    value(i, j),
+                    value(i + 1, j),
+                    value(i + 1, j + 1),
+                    value(i, j + 1),
+                ],
+            };
+            cell.refine(ast, axes, depth, &mut segments)?;
+        }
+    }
+
+    let epsilon = dx.min(dy) / (1 << depth) as f32 * 1e-3;
+    Ok(join(segments, epsilon))
+}
+
+struct Cell {
+    min: Vector,
+    size: Vector,
+    /// Values at the bottom left, bottom right, top right and top left corners.
+    corners: [f32; 4],
+}
+
+impl Cell {
+    fn crosses(&self) -> bool {
+        if self.corners.iter().any(|v| !v.is_finite()) {
+            return false;
+        }
+        let positive = self.corners.iter().filter(|v| **v > 0.0).count();
+        positive != 0 && positive != 4
+    }
+
+    fn refine(
+        &self,
+        ast: &IRSegment,
+        axes: Axes,
+        depth: u32,
+        segments: &mut Vec<(Vector, Vector)>,
+    ) -> Result<()> {
+        if !self.crosses() {
+            return Ok(());
+        }
+        if depth == 0 {
+            self.march(ast, axes, segments)?;
+            return Ok(());
+        }
+
+        let half = self.size * 0.5;
+        let at = |x: f32, y: f32| eval_view(ast, axes, Vector::new(x, y));
+        let (x0, y0) = (self.min.x, self.min.y);
+        let (x1, y1) = (x0 + half.x, y0 + half.y);
+        let (x2, y2) = (x0 + self.size.x, y0 + self.size.y);
+
+        let [bl, br, tr, tl] = self.corners;
+        let bottom = at(x1, y0)?;
+        let right = at(x2, y1)?;
+        let top = at(x1, y2)?;
+        let left = at(x0, y1)?;
+        let center = at(x1, y1)?;
+
+        for (min, corners) in [
+            (Vector::new(x0, y0), [bl, bottom, center, left]),
+            (Vector::new(x1, y0), [bottom, br, right, center]),
+            (Vector::new(x1, y1), [center, right, tr, top]),
+            (Vector::new(x0, y1), [left, center, top, tl]),
+        ] {
+            Cell {
+                min,
+                size: half,
+                corners,
+            }
+            .refine(ast, axes, depth - 1, segments)?;
+        }
+        Ok(())
+    }
+
+    fn march(
+        &self,
+        ast: &IRSegment,
+        axes: Axes,
+        segments: &mut Vec<(Vector, Vector)>,
+    ) -> Result<()> {
+        let [bl, br, tr, tl] = self.corners;
+        let (x0, y0) = (self.min.x, self.min.y);
+        let (x1, y1) = (x0 + self.size.x, y0 + self.size.y);
+
+        let lerp = |a: f32, b: f32| a / (a - b);
+        let bottom = || Vector::new(x0 + lerp(bl, br) * self.size.x, y0);
+        let right = || Vector::new(x1, y0 + lerp(br, tr) * self.size.y);
+        let top = || Vector::new(x0 + lerp(tl, tr) * self.size.x, y1);
+        let left = || Vector::new(x0, y0 + lerp(bl, tl) * self.size.y);
+
+        let case = (bl > 0.0) as u8
+            | ((br > 0.0) as u8) << 1
+            | ((tr > 0.0) as u8) << 2
+            | ((tl > 0.0) as u8) << 3;
+
+        match case {
+            1 | 14 => segments.push((left(), bottom())),
+            2 | 13 => segments.push((bottom(), right())),
+            3 | 12 => segments.push((left(), right())),
+            4 | 11 => segments.push((right(), top())),
+            6 | 9 => segments.push((bottom(), top())),
+            7 | 8 => segments.push((left(), top())),
+            5 | 10 => {
+                // Saddle, the sign of the center decides which corners are connected.
+                let center = eval_view(
+                    ast,
+                    axes,
+                    Vector::new(x0 + self.size.x / 2.0, y0 + self.size.y / 2.0),
+                )?;
+                if (center > 0.0) == (case == 5) {
+                    segments.push((left(), top()));
+                    segments.push((bottom(), right()));
+                } else {
+                    segments.push((left(), bottom()));
+                    segments.push((right(), top()));
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+/// Chains segments that share endpoints into polylines.
+fn join(segments: Vec<(Vector, Vector)>, epsilon: f32) -> Vec<Option<Vector>> {
+    let key = |p: Vector| {
+        (
+            (p.x / epsilon).round() as i64,
+            (p.y / epsilon).round() as i64,
+        )
+    };
+
+    let mut ends: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+    for (i, (a, b)) in segments.iter().enumerate() {
+        ends.entry(key(*a)).or_default().push(i);
+        ends.entry(key(*b)).or_default().push(i);
+    }
+
+    let mut used = vec![false; segments.len()];
+    let next = |point: Vector, used: &mut [bool]| {
+        let i = *ends.get(&key(point))?.iter().find(|i| !used[**i])?;
+        used[i] = true;
+        let (a, b) = segments[i];
+        Some(if key(a) == key(point) { b } else { a })
+    };
+
+    let mut points = Vec::new();
+    for i in 0..segments.len() {
+        if used[i] {
+            continue;
+        }
+        used[i] = true;
+        let (a, b) = segments[i];
+
+        let mut backwards = vec![a];
+        let mut end = a;
+        while let Some(p) = next(end, &mut used) {
+            backwards.push(p);
+            end = p;
+        }
+        points.extend(backwards.into_iter().rev().map(Some));
+
+        let mut end = b;
+        points.push(Some(b));
+        while let Some(p) = next(end, &mut used) {
+            points.push(Some(p));
+            end = p;
+        }
+        points.push(None);
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell(corners: [f32; 4]) -> Cell {
+        Cell {
+            min: Vector::new(0.0, 0.0),
+            size: Vector::new(1.0, 1.0),
+            corners,
+        }
+    }
+
+    #[test]
+    fn cells_cross_on_mixed_signs() {
+        assert!(cell([-1.0, 1.0, 1.0, 1.0]).crosses());
+        assert!(cell([1.0, -1.0, -1.0, 1.0]).crosses());
+        assert!(!cell([1.0, 2.0, 3.0, 4.0]).crosses());
+        assert!(!cell([-1.0, -2.0, -3.0, -4.0]).crosses());
+        assert!(!cell([-1.0, 1.0, f32::NAN, 1.0]).crosses());
+    }
+
+    #[test]
+    fn segments_are_chained() {
+        let (a, b, c, d) = (
+            Vector::new(0.0, 0.0),
+            Vector::new(1.0, 0.0),
+            Vector::new(1.0, 1.0),
+            Vector::new(2.0, 1.0),
+        );
+        // Out of order and pointing either way, as marching squares leaves them.
+        let points = join(vec![(b, c), (b, a), (d, c)], 1e-3);
+        assert_eq!(points, vec![Some(a), Some(b), Some(c), Some(d), None]);
+    }
+
+    #[test]
+    fn separate_curves_stay_apart() {
+        let (a, b) = (Vector::new(0.0, 0.0), Vector::new(1.0, 0.0));
+        let (c, d) = (Vector::new(5.0, 5.0), Vector::new(6.0, 5.0));
+        let points = join(vec![(a, b), (c, d)], 1e-3);
+        assert_eq!(points, vec![Some(a), Some(b), None, Some(c), Some(d), None]);
+    }
+
+    #[test]
+    fn nearly_equal_endpoints_are_joined() {
+        let (a, b) = (Vector::new(0.0, 0.0), Vector::new(1.0, 0.0));
+        let (b2, c) = (Vector::new(1.0 + 1e-7, 0.0), Vector::new(2.0, 0.0));
+        let points = join(vec![(a, b), (b2, c)], 1e-3);
+        assert_eq!(points.iter().filter(|p| p.is_none()).count(), 1);
+        assert_eq!(points.len(), 4);
+    }
+}
@@ -0,0 +1,353 @@
+use iced::{
+    alignment::{Horizontal, Vertical},
+    widget::canvas::{Frame, Path, Stroke, Text},
+    Color, Point, Vector,
+};
+
+use super::{translate_coord, AxisScale, GraphSettings};
+
+/// Smallest distance in pixels between two major grid lines.
+const MIN_MAJOR_SPACING: f32 = 80.0;
+const LABEL_SIZE: f32 = 14.0;
+const TITLE_SIZE: f32 = 16.0;
+/// Space left between a label and the axis it belongs to.
+const LABEL_MARGIN: f32 = 4.0;
+const TICK_LENGTH: f32 = 6.0;
+
+const MINOR_COLOR: Color = Color::from_rgb(0.94, 0.94, 0.94);
+const MAJOR_COLOR: Color = Color::from_rgb(0.82, 0.82, 0.82);
+const AXIS_COLOR: Color = Color::from_rgb(0.2, 0.2, 0.2);
+const LABEL_COLOR: Color = Color::from_rgb(0.25, 0.25, 0.25);
+
+/// Spacing between grid lines, picked from 1, 2 and 5 times a power of ten.
+#[derive(Debug, Clone, Copy)]
+pub struct Spacing {
+    pub major: f32,
+    pub minor: f32,
+    exponent: i32,
+}
+
+impl Spacing {
+    /// Picks the smallest spacing keeping major lines at least [`MIN_MAJOR_SPACING`] pixels apart.
+    pub fn new(scale: f32) -> Self {
+        let min = MIN_MAJOR_SPACING / scale;
+        let exponent = min.log10().floor() as i32;
+        let power = 10f32.powi(exponent);
+        let (mantissa, divisions) = [(1.0, 5.0), (2.0, 4.0), (5.0, 5.0), (10.0, 5.0)]
+            .into_iter()
+            .find(|(mantissa, _)| mantissa * power >= min)
+            .unwrap_or((10.0, 5.0));
+        let major = mantissa * power;
+        Self {
+            major,
+            minor: major / divisions,
+            exponent: if mantissa == 10.0 {
+                exponent + 1
+            } else {
+                exponent
+            },
+        }
+    }
+
+    /// Formats a multiple of the major spacing without floating point noise.
+    pub fn label(&self, value: f32) -> String {
+        if value == 0.0 {
+            return "0".to_string();
+        }
+        if !(-5..=6).contains(&self.exponent) {
+            return format!("{value:e}");
+        }
+        let decimals = (-self.exponent).max(0) as usize;
+        format!("{value:.decimals$}").replace('-', "−")
+    }
+}
+
+/// Grid lines along one axis, in the coordinates the view is linear in.
+struct Ticks {
+    minor: Vec<f32>,
+    /// Major lines and their labels.
+    major: Vec<(f32, String)>,
+}
+
+impl Ticks {
+    fn new(axis: AxisScale, scale: f32, min: f32, max: f32) -> Self {
+        match axis {
+            AxisScale::Linear => {
+                let spacing = Spacing::new(scale);
+                Self {
+                    minor: multiples(spacing.minor, min, max).collect(),
+                    major: multiples(spacing.major, min, max)
+                        .map(|v| (v, spacing.label(v)))
+                        .collect(),
+                }
+            }
+            AxisScale::Log => {
+                // Powers of ten are major lines, skipping some when they are too close together.
+                let step = (MIN_MAJOR_SPACING / scale).ceil().max(1.0);
+                let major = multiples(step, min, max)
+                    .map(|exponent| (exponent, power_label(exponent as i32)))
+                    .collect();
+                let minor = if step == 1.0 {
+                    (min.floor() as i32..=max.ceil() as i32)
+                        .flat_map(|exponent| {
+                            (2..10).map(move |k| exponent as f32 + (k as f32).log10())
+                        })
+                        .filter(|v| (min..=max).contains(v))
+                        .collect()
+                } else {
+                    multiples(1.0, min, max).collect()
+                };
+                Self { minor, major }
+            }
+        }
+    }
+}
+
+/// Labels `10^exponent` like [`Spacing::label`] labels linear axes.
+fn power_label(exponent: i32) -> String {
+    if (-5..=6).contains(&exponent) {
+        let decimals = (-exponent).max(0) as usize;
+        format!("{:.decimals$}", 10f64.powi(exponent))
+    } else {
+        format!("1e{exponent}")
+    }
+}
+
+/// Draws grid lines, axes and axis labels for the given viewport. Each axis gets its own spacing,
+/// since `scale` can differ between them.
+pub fn draw(frame: &mut Frame, mid: Vector, scale: Vector, settings: &GraphSettings) {
+    let size = frame.size();
+    let axes = settings.axes;
+
+    let screen_x = |x: f32| translate_coord(x, mid.x, scale.x, size.width);
+    let screen_y = |y: f32| translate_coord(y, mid.y, -scale.y, size.height);
+    let (min_x, max_x) = (
+        mid.x - size.width / 2.0 / scale.x,
+        mid.x + size.width / 2.0 / scale.x,
+    );
+    let (min_y, max_y) = (
+        mid.y - size.height / 2.0 / scale.y,
+        mid.y + size.height / 2.0 / scale.y,
+    );
+    let ticks_x = Ticks::new(axes.x, scale.x, min_x, max_x);
+    let ticks_y = Ticks::new(axes.y, scale.y, min_y, max_y);
+
+    let vertical = |frame: &mut Frame, x: f32, stroke: Stroke| {
+        frame.stroke(
+            &Path::line(Point::new(x, 0.0), Point::new(x, size.height)),
+            stroke,
+        );
+    };
+    let horizontal = |frame: &mut Frame, y: f32, stroke: Stroke| {
+        frame.stroke(
+            &Path::line(Point::new(0.0, y), Point::new(size.width, y)),
+            stroke,
+        );
+    };
+
+    if settings.show_grid {
+        let minor = Stroke::default().with_width(1.0).with_color(MINOR_COLOR);
+        let major = Stroke::default().with_width(1.0).with_color(MAJOR_COLOR);
+        for x in &ticks_x.minor {
+            vertical(frame, screen_x(*x), minor);
+        }
+        for y in &ticks_y.minor {
+            horizontal(frame, screen_y(*y), minor);
+        }
+        for (x, _) in &ticks_x.major {
+            vertical(frame, screen_x(*x), major);
+        }
+        for (y, _) in &ticks_y.major {
+            horizontal(frame, screen_y(*y), major);
+        }
+    }
+
+    // A log axis has no zero for the other axis to cross it at, so that axis isn't drawn and its
+    // labels stay at the edge of the viewport.
+    let origin_x = match axes.x {
+        AxisScale::Linear => screen_x(0.0),
+        AxisScale::Log => f32::NEG_INFINITY,
+    };
+    let origin_y = match axes.y {
+        AxisScale::Linear => screen_y(0.0),
+        AxisScale::Log => f32::INFINITY,
+    };
+
+    let axis = Stroke::default().with_width(1.5).with_color(AXIS_COLOR);
+    // The y axis is the vertical line through `origin_x`, and the x axis the horizontal one.
+    if settings.show_y_axis && origin_x.is_finite() {
+        vertical(frame, origin_x, axis);
+        for (y, _) in &ticks_y.major {
+            let y = screen_y(*y);
+            frame.stroke(
+                &Path::line(
+                    Point::new(origin_x - TICK_LENGTH / 2.0, y),
+                    Point::new(origin_x + TICK_LENGTH / 2.0, y),
+                ),
+                axis,
+            );
+        }
+    }
+    if settings.show_x_axis && origin_y.is_finite() {
+        horizontal(frame, origin_y, axis);
+        for (x, _) in &ticks_x.major {
+            let x = screen_x(*x);
+            frame.stroke(
+                &Path::line(
+                    Point::new(x, origin_y - TICK_LENGTH / 2.0),
+                    Point::new(x, origin_y + TICK_LENGTH / 2.0),
+                ),
+                axis,
+            );
+        }
+    }
+
+    // Labels follow the axes, but stick to the edge of the viewport when the axis is off-screen.
+    if settings.show_x_labels {
+        let label_y = (origin_y + LABEL_MARGIN).clamp(LABEL_MARGIN, size.height - LABEL_SIZE);
+        for (x, label) in &ticks_x.major {
+            if *x == 0.0 && axes.x == AxisScale::Linear {
+                continue;
+            }
+            frame.fill_text(Text {
+                content: label.clone(),
+                position: Point::new(screen_x(*x), label_y),
+                color: LABEL_COLOR,
+                size: LABEL_SIZE.into(),
+                horizontal_alignment: Horizontal::Center,
+                vertical_alignment: Vertical::Top,
+                ..Text::default()
+            });
+        }
+    }
+
+    if settings.show_y_labels {
+        let (label_x, alignment) = if origin_x - LABEL_MARGIN < LABEL_SIZE * 3.0 {
+            (origin_x.max(0.0) + LABEL_MARGIN, Horizontal::Left)
+        } else {
+            (
+                (origin_x - LABEL_MARGIN).min(size.width - LABEL_MARGIN),
+                Horizontal::Right,
+            )
+        };
+        for (y, label) in &ticks_y.major {
+            if *y == 0.0 && axes.y == AxisScale::Linear {
+                continue;
+            }
+            frame.fill_text(Text {
+                content: label.clone(),
+                position: Point::new(label_x, screen_y(*y)),
+                color: LABEL_COLOR,
+                size: LABEL_SIZE.into(),
+                horizontal_alignment: alignment,
+                vertical_alignment: Vertical::Center,
+                ..Text::default()
+            });
+        }
+    }
+
+    // The origin is labelled once, for whichever axes are numbered.
+    let numbered = settings.show_x_labels || settings.show_y_labels;
+    if numbered && origin_x.is_finite() && origin_y.is_finite() {
+        frame.fill_text(Text {
+            content: "0".to_string(),
+            position: Point::new(origin_x - LABEL_MARGIN, origin_y + LABEL_MARGIN),
+            color: LABEL_COLOR,
+            size: LABEL_SIZE.into(),
+            horizontal_alignment: Horizontal::Right,
+            vertical_alignment: Vertical::Top,
+            ..Text::default()
+        });
+    }
+
+    // Titles go at the far ends of the axes.
+    if !settings.x_title.is_empty() {
+        frame.fill_text(Text {
+            content: settings.x_title.clone(),
+            position: Point::new(
+                size.width - LABEL_MARGIN,
+                (origin_y - LABEL_MARGIN)
+                    .clamp(TITLE_SIZE + LABEL_MARGIN, size.height - LABEL_SIZE),
+            ),
+            color: AXIS_COLOR,
+            size: TITLE_SIZE.into(),
+            horizontal_alignment: Horizontal::Right,
+            vertical_alignment: Vertical::Bottom,
+            ..Text::default()
+        });
+    }
+    if !settings.y_title.is_empty() {
+        frame.fill_text(Text {
+            content: settings.y_title.clone(),
+            position: Point::new(
+                (origin_x + LABEL_MARGIN).clamp(LABEL_MARGIN, size.width - LABEL_MARGIN),
+                LABEL_MARGIN,
+            ),
+            color: AXIS_COLOR,
+            size: TITLE_SIZE.into(),
+            horizontal_alignment: Horizontal::Left,
+            vertical_alignment: Vertical::Top,
+            ..Text::default()
+        });
+    }
+}
+
+/// Multiples of `step` between `min` and `max`.
+fn multiples(step: f32, min: f32, max: f32) -> impl Iterator<Item = f32> {
+    let first = (min / step).ceil() as i64;
+    let last = (max / step).floor() as i64;
+    (first..=last).map(move |i| i as f32 * step)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn spacing_steps_through_1_2_5() {
+        let spacing = Spacing::new(40.0);
+        assert!(close(spacing.major, 2.0) && close(spacing.minor, 0.5));
+        let spacing = Spacing::new(20.0);
+        assert!(close(spacing.major, 5.0) && close(spacing.minor, 1.0));
+        // 0.8 units round up to the next power of ten.
+        let spacing = Spacing::new(100.0);
+        assert!(close(spacing.major, 1.0) && close(spacing.minor, 0.2));
+    }
+
+    #[test]
+    fn labels_have_as_many_decimals_as_the_spacing() {
+        let spacing = Spacing::new(100.0);
+        assert_eq!(spacing.label(3.0), "3");
+        assert_eq!(spacing.label(-2.0), "−2");
+        assert_eq!(spacing.label(0.0), "0");
+        let spacing = Spacing::new(1000.0);
+        assert_eq!(spacing.label(0.3), "0.3");
+        assert_eq!(spacing.label(-0.3), "−0.3");
+    }
+
+    #[test]
+    fn far_out_labels_use_exponents() {
+        let spacing = Spacing::new(1e-6);
+        assert_eq!(spacing.label(1e8), "1e8");
+    }
+
+    #[test]
+    fn multiples_in_range() {
+        let values: Vec<f32> = multiples(0.5, -1.2, 1.2).collect();
+        assert_eq!(values, vec![-1.0, -0.5, 0.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn log_ticks() {
+        let ticks = Ticks::new(AxisScale::Log, 100.0, -1.0, 2.0);
+        let labels: Vec<&str> = ticks.major.iter().map(|(_, l)| l.as_str()).collect();
+        assert_eq!(labels, vec!["0.1", "1", "10", "100"]);
+        // 2 to 9 times each power of ten below the last.
+        assert_eq!(ticks.minor.len(), 24);
+        assert_eq!(power_label(7), "1e7");
+    }
+}
@@ -0,0 +1,286 @@
+use anyhow::Result;
+use desmoxide::lang::compiler::ir::IRSegment;
+use iced::{
+    widget::canvas::{path, Frame, Path},
+    Color, Point, Size, Vector,
+};
+
+use super::{eval_view, translate_point, untranslate_point, Axes, PlotKind};
+
+/// Size in pixels of the cells two-variable inequalities are filled with.
+const FILL_CELL: f32 = 4.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+}
+
+impl Comparison {
+    pub fn is_strict(self) -> bool {
+        matches!(self, Comparison::Less | Comparison::Greater)
+    }
+
+    /// Whether `value <op> 0` holds.
+    pub fn holds(self, value: f32) -> bool {
+        match self {
+            Comparison::Less => value < 0.0,
+            Comparison::LessEq => value <= 0.0,
+            Comparison::Greater => value > 0.0,
+            Comparison::GreaterEq => value >= 0.0,
+        }
+    }
+
+    fn flip(self) -> Self {
+        match self {
+            Comparison::Less => Comparison::Greater,
+            Comparison::LessEq => Comparison::GreaterEq,
+            Comparison::Greater => Comparison::Less,
+            Comparison::GreaterEq => Comparison::LessEq,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Inequality {
+    pub comparison: Comparison,
+    /// [`PlotKind::Function`] when shading above or below `y = f(x)`, and [`PlotKind::Relation`]
+    /// when shading wherever `f(x, y) <op> 0`.
+    pub kind: PlotKind,
+}
+
+const OPERATORS: [(&str, Comparison); 8] = [
+    ("\\leq", Comparison::LessEq),
+    ("\\geq", Comparison::GreaterEq),
+    ("\\le", Comparison::LessEq),
+    ("\\ge", Comparison::GreaterEq),
+    ("≤", Comparison::LessEq),
+    ("≥", Comparison::GreaterEq),
+    ("<", Comparison::Less),
+    (">", Comparison::Greater),
+];
+
+/// Splits an inequality into the expression to compile and how to shade it.
+///
+/// `y < f(x)` and `f(x) > y` become `f(x)` shaded against `y`, everything else becomes
+/// `lhs - rhs` compared against zero.
+pub fn parse(latex: &str) -> Option<(String, Inequality)> {
+    let (lhs, comparison, rhs) = split(latex)?;
+    let (lhs, rhs) = (lhs.trim(), rhs.trim());
+    let function = |comparison| Inequality {
+        comparison,
+        kind: PlotKind::Function,
+    };
+    if lhs == "y" && !rhs.contains('y') {
+        Some((rhs.to_string(), function(comparison)))
+    } else if rhs == "y" && !lhs.contains('y') {
+        Some((lhs.to_string(), function(comparison.flip())))
+    } else {
+        Some((
+            format!("\\left({lhs}\\right)-\\left({rhs}\\right)"),
+            Inequality {
+                comparison,
+                kind: PlotKind::Relation,
+            },
+        ))
+    }
+}
+
+/// Finds the comparison at the top level of `latex`. Comparisons inside braces or parentheses,
+/// like the conditions of `\left\{x<0:-x,x\right\}`, belong to the expression around them, and
+/// definitions and piecewise branches aren't inequalities.
+fn split(latex: &str) -> Option<(&str, Comparison, &str)> {
+    let mut found = None;
+    let mut depth = 0;
+    for (start, c) in latex.char_indices() {
+        match c {
+            '{' | '(' | '[' => depth += 1,
+            '}' | ')' | ']' => depth -= 1,
+            '=' | ':' if depth == 0 => return None,
+            _ => (),
+        }
+        if depth != 0 || found.is_some() {
+            continue;
+        }
+        let rest = &latex[start..];
+        found = OPERATORS.into_iter().find_map(|(operator, comparison)| {
+            let after = rest.strip_prefix(operator)?;
+            // `\le` is also the start of `\left`.
+            let command = operator.starts_with('\\');
+            if command && after.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                return None;
+            }
+            Some((&latex[..start], comparison, after))
+        });
+    }
+    found
+}
+
+/// Fills the region between the curve `y = f(x)` and the top or bottom of the frame.
+pub fn fill_function(
+    frame: &mut Frame,
+    points: &[Option<Vector>],
+    comparison: Comparison,
+    mid: Vector,
+    scale: Vector,
+    color: Color,
+) {
+    let size = frame.size();
+    let edge = match comparison {
+        Comparison::Less | Comparison::LessEq => size.height,
+        Comparison::Greater | Comparison::GreaterEq => 0.0,
+    };
+    let clamp = |p: Point| Point::new(p.x, p.y.clamp(-size.height, size.height * 2.0));
+
+    let region = Path::new(|builder| {
+        for run in points.split(Option::is_none) {
+            let run: Vec<Point> = run
+                .iter()
+                .flatten()
+                .map(|p| clamp(translate_point(*p, mid, scale, size)))
+                .collect();
+            let (Some(first), Some(last)) = (run.first(), run.last()) else {
+                continue;
+            };
+            builder.move_to(Point::new(first.x, edge));
+            for point in &run {
+                builder.line_to(*point);
+            }
+            builder.line_to(Point::new(last.x, edge));
+            builder.close();
+        }
+    });
+    frame.fill(&region, color);
+}
+
+/// Fills the cells of the frame where `ast(x, y) <op> 0` holds at the cell's center.
+pub fn fill_relation(
+    frame: &mut Frame,
+    ast: &IRSegment,
+    comparison: Comparison,
+    axes: Axes,
+    mid: Vector,
+    scale: Vector,
+    color: Color,
+) -> Result<()> {
+    let size = frame.size();
+    let columns = (size.width / FILL_CELL).ceil() as u32;
+    let rows = (size.height / FILL_CELL).ceil() as u32;
+    let to_graph = |px: f32, py: f32| untranslate_point(Point::new(px, py), mid, scale, size);
+
+    let mut region = path::Builder::new();
+    for j in 0..rows {
+        let py = j as f32 * FILL_CELL;
+        // Consecutive cells on a row are merged into one rectangle.
+        let mut run_start = None;
+        for i in 0..=columns {
+            let px = i as f32 * FILL_CELL;
+            let inside = i < columns && {
+                let p = to_graph(px + FILL_CELL / 2.0, py + FILL_CELL / 2.0);
+                comparison.holds(eval_view(ast, axes, p)?)
+            };
+            match (inside, run_start) {
+                (true, None) => run_start = Some(px),
+                (false, Some(start)) => {
+                    region.rectangle(Point::new(start, py), Size::new(px - start, FILL_CELL));
+                    run_start = None;
+                }
+                _ => (),
+            }
+        }
+    }
+    frame.fill(&region.build(), color);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(comparison: Comparison) -> Inequality {
+        Inequality {
+            comparison,
+            kind: PlotKind::Function,
+        }
+    }
+
+    #[test]
+    fn functions_are_split_from_y() {
+        assert_eq!(
+            parse("y<x^2"),
+            Some(("x^2".to_string(), function(Comparison::Less)))
+        );
+        assert_eq!(
+            parse("y \\geq \\sin x"),
+            Some(("\\sin x".to_string(), function(Comparison::GreaterEq)))
+        );
+    }
+
+    #[test]
+    fn y_on_the_right_flips_the_comparison() {
+        assert_eq!(
+            parse("x^2>y"),
+            Some(("x^2".to_string(), function(Comparison::Less)))
+        );
+        assert_eq!(
+            parse("2x≤y"),
+            Some(("2x".to_string(), function(Comparison::GreaterEq)))
+        );
+    }
+
+    #[test]
+    fn relations_compare_the_difference() {
+        assert_eq!(
+            parse("\\left(x\\right)^2+y^2\\le 1"),
+            Some((
+                "\\left(\\left(x\\right)^2+y^2\\right)-\\left(1\\right)".to_string(),
+                Inequality {
+                    comparison: Comparison::LessEq,
+                    kind: PlotKind::Relation,
+                },
+            ))
+        );
+    }
+
+    #[test]
+    fn equations_are_not_inequalities() {
+        assert_eq!(parse("y=x"), None);
+        assert_eq!(parse("y=\\left(x+1\\right)"), None);
+    }
+
+    #[test]
+    fn piecewise_conditions_are_not_inequalities() {
+        assert_eq!(parse("y=\\left\\{x<0:-x,x\\right\\}"), None);
+        assert_eq!(parse("f(x)=\\left\\{x>0:1\\right\\}"), None);
+        assert_eq!(parse("\\left\\{x\\le0:1,2\\right\\}"), None);
+    }
+
+    #[test]
+    fn restrictions_are_not_inequalities() {
+        assert_eq!(parse("y=x\\left\\{0<x<1\\right\\}"), None);
+        assert_eq!(parse("x^2\\left\\{x>0\\right\\}"), None);
+    }
+
+    #[test]
+    fn comparisons_inside_groups_are_skipped() {
+        assert_eq!(
+            parse("y>\\left\\{x<0:-x,x\\right\\}"),
+            Some((
+                "\\left\\{x<0:-x,x\\right\\}".to_string(),
+                function(Comparison::Greater)
+            ))
+        );
+    }
+
+    #[test]
+    fn strictness() {
+        assert!(!Comparison::Less.holds(0.0));
+        assert!(Comparison::LessEq.holds(0.0));
+        assert!(Comparison::Greater.holds(1.0));
+        assert!(!Comparison::GreaterEq.holds(-1.0));
+        assert!(Comparison::Greater.is_strict());
+        assert!(!Comparison::GreaterEq.is_strict());
+    }
+}
@@ -0,0 +1,264 @@
+use anyhow::Result;
+use iced::Vector;
+
+/// Number of evenly spaced intervals the domain is split into before refining.
+const INITIAL_INTERVALS: u32 = 64;
+/// Maximum number of times a single initial interval is halved.
+const MAX_DEPTH: u32 = 12;
+/// Distance in pixels a midpoint may stray from its chord before the interval is split.
+const PIXEL_TOLERANCE: f32 = 0.5;
+/// Turning angle in radians above which an interval is split, as long as it spans a pixel.
+const MAX_ANGLE: f32 = 0.1;
+/// Length in pixels above which a final segment can be a discontinuity.
+const JUMP_THRESHOLD: f32 = 2.0;
+/// How many times longer than the segments next to it a final segment has to be before it is
+/// checked for being a discontinuity. Smooth curves, however steep, change length gradually.
+const STEEPNESS: f32 = 4.0;
+/// Most bisection steps used to confirm a discontinuity.
+const BISECTIONS: u32 = 32;
+/// Bisection steps in a row the gap has to stay about the same for it to count as a jump.
+const STALLED_STEPS: u32 = 3;
+
+/// Samples the curve `f(t)` for `t` in `min..max`, subdividing intervals where the curve bends
+/// or moves more than [`PIXEL_TOLERANCE`] away from a straight line at the given `scale`, in pixels
+/// per unit along each axis.
+///
+/// Segments that cross a jump, a pole or a gap where the curve is undefined are broken up by a
+/// `None`. At most around `budget` points are produced.
+pub fn sample(
+    f: impl Fn(f32) -> Result<Vector>,
+    min: f32,
+    max: f32,
+    scale: Vector,
+    budget: u32,
+) -> Result<Vec<Option<Vector>>> {
+    let intervals = INITIAL_INTERVALS.min(budget.max(1));
+    let mut sampler = Sampler {
+        f,
+        scale,
+        points: Vec::with_capacity(budget as usize),
+        interval_budget: (budget / intervals).max(2) as usize,
+        interval_start: 0,
+    };
+
+    let dt = (max - min) / intervals as f32;
+    let mut a = min;
+    let mut pa = (sampler.f)(a)?;
+    sampler.push(pa);
+    for i in 1..=intervals {
+        let b = min + i as f32 * dt;
+        let pb = (sampler.f)(b)?;
+        sampler.interval_start = sampler.points.len();
+        sampler.subdivide(a, pa, b, pb, 0)?;
+        a = b;
+        pa = pb;
+    }
+
+    Ok(sampler.points)
+}
+
+struct Sampler<F> {
+    f: F,
+    scale: Vector,
+    points: Vec<Option<Vector>>,
+    interval_budget: usize,
+    interval_start: usize,
+}
+
+impl<F: Fn(f32) -> Result<Vector>> Sampler<F> {
+    /// Pushes the points after `a` up to and including `b`.
+    fn subdivide(&mut self, a: f32, pa: Vector, b: f32, pb: Vector, depth: u32) -> Result<()> {
+        let m = (a + b) / 2.0;
+        let pm = (self.f)(m)?;
+
+        let spent = self.points.len() - self.interval_start;
+        if depth < MAX_DEPTH && spent < self.interval_budget && self.needs_split(pa, pm, pb) {
+            self.subdivide(a, pa, m, pm, depth + 1)?;
+            self.subdivide(m, pm, b, pb, depth + 1)?;
+        } else {
+            // The last segment drawn, to compare these against.
+            let before = match self.points[..] {
+                [.., Some(p), Some(q)] => self.length(q - p),
+                _ => f32::INFINITY,
+            };
+            let (first, second) = (self.length(pm - pa), self.length(pb - pm));
+            self.push_segment(a, pa, m, pm, second.min(before))?;
+            self.push_segment(m, pm, b, pb, first.min(before))?;
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, p: Vector) {
+        if finite(p) {
+            self.points.push(Some(p));
+        } else if self.points.last().is_some_and(Option::is_some) {
+            self.points.push(None);
+        }
+    }
+
+    /// Pushes `pb`, lifting the pen first if the curve is discontinuous between `a` and `b`.
+    /// Only segments much longer than `neighbour`, the length of a segment next to it in pixels,
+    /// are checked.
+    fn push_segment(
+        &mut self,
+        a: f32,
+        pa: Vector,
+        b: f32,
+        pb: Vector,
+        neighbour: f32,
+    ) -> Result<()> {
+        let length = self.length(pb - pa);
+        if finite(pa)
+            && finite(pb)
+            && self.points.last().is_some_and(Option::is_some)
+            && length > JUMP_THRESHOLD
+            && length > neighbour * STEEPNESS
+            && self.is_discontinuous(a, pa, b, pb)?
+        {
+            self.points.push(None);
+        }
+        self.push(pb);
+        Ok(())
+    }
+
+    /// Bisects towards the steepest part of the segment. The gap left shrinks away for
+    /// continuous curves, but stays or grows for jumps, poles and gaps.
+    fn is_discontinuous(
+        &self,
+        mut a: f32,
+        mut pa: Vector,
+        mut b: f32,
+        mut pb: Vector,
+    ) -> Result<bool> {
+        let mut gap = self.length(pb - pa);
+        let mut stalled = 0;
+        for _ in 0..BISECTIONS {
+            let m = (a + b) / 2.0;
+            if m <= a || m >= b {
+                break;
+            }
+            let pm = (self.f)(m)?;
+            if !finite(pm) {
+                return Ok(true);
+            }
+            if self.length(pm - pa) > self.length(pb - pm) {
+                (b, pb) = (m, pm);
+            } else {
+                (a, pa) = (m, pm);
+            }
+
+            let next = self.length(pb - pa);
+            if next < 1.0 {
+                return Ok(false);
+            }
+            stalled = if next > gap * 0.75 { stalled + 1 } else { 0 };
+            if stalled == STALLED_STEPS {
+                return Ok(true);
+            }
+            gap = next;
+        }
+        Ok(gap > 1.0)
+    }
+
+    fn needs_split(&self, pa: Vector, pm: Vector, pb: Vector) -> bool {
+        match (finite(pa), finite(pm), finite(pb)) {
+            (true, true, true) => (),
+            (false, false, false) => return false,
+            // Narrow down where the curve stops being defined.
+            _ => return true,
+        }
+
+        let chord_mid = (pa + pb) * 0.5;
+        let error = self.pixels(pm - chord_mid);
+        if error.x.hypot(error.y) > PIXEL_TOLERANCE {
+            return true;
+        }
+
+        let first = self.pixels(pm - pa);
+        let second = self.pixels(pb - pm);
+        let (first_len, second_len) = (first.x.hypot(first.y), second.x.hypot(second.y));
+        if first_len + second_len < 1.0 {
+            return false;
+        }
+        let angle = (first.x * second.y - first.y * second.x)
+            .atan2(first.x * second.x + first.y * second.y)
+            .abs();
+        angle > MAX_ANGLE
+    }
+
+    /// The on-screen length of `v` along each axis.
+    fn pixels(&self, v: Vector) -> Vector {
+        Vector::new(v.x * self.scale.x, v.y * self.scale.y)
+    }
+
+    /// The on-screen length of `v`.
+    fn length(&self, v: Vector) -> f32 {
+        let d = self.pixels(v);
+        d.x.hypot(d.y)
+    }
+}
+
+fn finite(p: Vector) -> bool {
+    p.x.is_finite() && p.y.is_finite()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCALE: Vector = Vector { x: 100.0, y: 100.0 };
+
+    fn runs(points: &[Option<Vector>]) -> Vec<Vec<Vector>> {
+        points
+            .split(Option::is_none)
+            .map(|run| run.iter().flatten().copied().collect::<Vec<_>>())
+            .filter(|run| !run.is_empty())
+            .collect()
+    }
+
+    #[test]
+    fn smooth_curves_are_not_broken() {
+        let points = sample(|t| Ok(Vector::new(t, t * t)), -2.0, 2.0, SCALE, 4000).unwrap();
+        assert!(points.iter().all(Option::is_some));
+        assert!(points.windows(2).all(|w| w[0].unwrap().x < w[1].unwrap().x));
+    }
+
+    #[test]
+    fn steps_are_broken_once() {
+        let step = |t: f32| Ok(Vector::new(t, if t < 0.3 { 0.0 } else { 1.0 }));
+        let points = sample(step, -1.0, 1.0, SCALE, 4000).unwrap();
+        let runs = runs(&points);
+        assert_eq!(runs.len(), 2);
+        assert!(runs[0].iter().all(|p| p.x < 0.3 && p.y == 0.0));
+        assert!(runs[1].iter().all(|p| p.x >= 0.3 && p.y == 1.0));
+    }
+
+    #[test]
+    fn undefined_gaps_are_left_out() {
+        let gap = |t: f32| {
+            let y = if t > 0.2 && t < 0.4 { f32::NAN } else { t };
+            Ok(Vector::new(t, y))
+        };
+        let points = sample(gap, -1.0, 1.0, SCALE, 4000).unwrap();
+        let runs = runs(&points);
+        assert_eq!(runs.len(), 2);
+        // The ends of the gap are narrowed down by subdividing.
+        let (end, start) = (runs[0].last().unwrap(), runs[1].first().unwrap());
+        assert!(end.x <= 0.2 && end.x > 0.199, "{end:?}");
+        assert!(start.x >= 0.4 && start.x < 0.401, "{start:?}");
+    }
+
+    #[test]
+    fn budget_limits_points() {
+        let wave = |t: f32| Ok(Vector::new(t, (t * 10.0).sin()));
+        let small = sample(wave, -100.0, 100.0, SCALE, 500).unwrap();
+        let large = sample(wave, -100.0, 100.0, SCALE, 8000).unwrap();
+        assert!(small.len() < large.len(), "{} {}", small.len(), large.len());
+    }
+
+    #[test]
+    fn errors_are_passed_on() {
+        let failing = |_| Err(anyhow::anyhow!("no"));
+        assert!(sample(failing, 0.0, 1.0, SCALE, 100).is_err());
+    }
+}
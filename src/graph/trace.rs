@@ -0,0 +1,141 @@
+use desmoxide::graph::expressions::ExpressionId;
+use iced::{
+    alignment::{Horizontal, Vertical},
+    widget::canvas::{Frame, Path, Stroke, Text},
+    Color, Point, Size, Vector,
+};
+
+use super::{translate_point, Axes};
+
+/// How far in pixels from a curve the cursor can be for it to be traced.
+const TRACE_RADIUS: f32 = 12.0;
+const MARKER_RADIUS: f32 = 5.0;
+const POINT_OF_INTEREST_RADIUS: f32 = 4.0;
+const POINT_OF_INTEREST_COLOR: Color = Color::from_rgb(0.55, 0.55, 0.55);
+const LABEL_SIZE: f32 = 14.0;
+const LABEL_PADDING: f32 = 4.0;
+/// Gap between the marker and its label.
+const LABEL_OFFSET: f32 = 8.0;
+
+/// A point on a plotted curve, picked by hovering or clicking near it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Trace {
+    pub id: ExpressionId,
+    /// Where the point is on the graph, regardless of the axes' scales.
+    pub point: Vector,
+}
+
+/// The candidate closest to `cursor`, if one is close enough. Candidates are points on the graph
+/// together with the expression they belong to.
+pub fn nearest(
+    candidates: impl Iterator<Item = (ExpressionId, Vector)>,
+    cursor: Point,
+    mid: Vector,
+    scale: Vector,
+    size: Size,
+    axes: Axes,
+) -> Option<Trace> {
+    let mut nearest: Option<(f32, Trace)> = None;
+    for (id, point) in candidates {
+        let distance = translate_point(axes.forward(point), mid, scale, size).distance(cursor);
+        if distance < TRACE_RADIUS && nearest.is_none_or(|(d, _)| distance < d) {
+            nearest = Some((distance, Trace { id, point }));
+        }
+    }
+    nearest.map(|(_, trace)| trace)
+}
+
+/// Whether `cursor` is on the marker of `trace`.
+pub fn hits(
+    trace: Trace,
+    cursor: Point,
+    mid: Vector,
+    scale: Vector,
+    size: Size,
+    axes: Axes,
+) -> bool {
+    translate_point(axes.forward(trace.point), mid, scale, size).distance(cursor) < TRACE_RADIUS
+}
+
+/// Draws a marker on the traced point, with its coordinates next to it.
+pub fn draw(frame: &mut Frame, trace: Trace, color: Color, mid: Vector, scale: Vector, axes: Axes) {
+    let size = frame.size();
+    let position = translate_point(axes.forward(trace.point), mid, scale, size);
+    if !(position.x.is_finite() && position.y.is_finite()) {
+        return;
+    }
+
+    frame.fill(&Path::circle(position, MARKER_RADIUS), color);
+    frame.stroke(
+        &Path::circle(position, MARKER_RADIUS),
+        Stroke::default().with_width(1.5).with_color(Color::WHITE),
+    );
+
+    let label = format!(
+        "({}, {})",
+        format_coordinate(trace.point.x),
+        format_coordinate(trace.point.y)
+    );
+    // Canvas text can't be measured, so the box is sized from an average glyph width.
+    let box_size = Size::new(
+        label.chars().count() as f32 * LABEL_SIZE * 0.6 + LABEL_PADDING * 2.0,
+        LABEL_SIZE + LABEL_PADDING * 2.0,
+    );
+    // Keep the label on screen, flipping it to the other side of the marker near the edges.
+    let x = if position.x + LABEL_OFFSET + box_size.width > size.width {
+        position.x - LABEL_OFFSET - box_size.width
+    } else {
+        position.x + LABEL_OFFSET
+    };
+    let y = if position.y - LABEL_OFFSET - box_size.height < 0.0 {
+        position.y + LABEL_OFFSET
+    } else {
+        position.y - LABEL_OFFSET - box_size.height
+    };
+
+    let background = Path::rectangle(Point::new(x, y), box_size);
+    frame.fill(&background, Color::WHITE);
+    frame.stroke(
+        &background,
+        Stroke::default().with_width(1.0).with_color(color),
+    );
+    frame.fill_text(Text {
+        content: label,
+        position: Point::new(x + box_size.width / 2.0, y + box_size.height / 2.0),
+        color: Color::BLACK,
+        size: LABEL_SIZE.into(),
+        horizontal_alignment: Horizontal::Center,
+        vertical_alignment: Vertical::Center,
+        ..Text::default()
+    });
+}
+
+/// Draws the grey dot marking a root, extremum, intercept or intersection.
+pub fn draw_point_of_interest(
+    frame: &mut Frame,
+    point: Vector,
+    mid: Vector,
+    scale: Vector,
+    axes: Axes,
+) {
+    let position = translate_point(axes.forward(point), mid, scale, frame.size());
+    if position.x.is_finite() && position.y.is_finite() {
+        frame.fill(
+            &Path::circle(position, POINT_OF_INTEREST_RADIUS),
+            POINT_OF_INTEREST_COLOR,
+        );
+    }
+}
+
+/// Formats a coordinate with four decimals, dropping trailing zeros.
+fn format_coordinate(value: f32) -> String {
+    if value != 0.0 && !(1e-4..1e6).contains(&value.abs()) {
+        return format!("{value:.3e}");
+    }
+    let formatted = format!("{value:.4}");
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+    match trimmed {
+        "-0" => "0".to_string(),
+        trimmed => trimmed.replace('-', "−"),
+    }
+}
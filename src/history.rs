@@ -0,0 +1,150 @@
+use std::collections::{HashMap, HashSet};
+
+use desmoxide::graph::expressions::ExpressionId;
+use iced::time::{Duration, Instant};
+
+use crate::{graph::Domain, style::Style, variables::Slider};
+
+/// Edits of the same kind to the same expression closer together than this are undone at once.
+const COALESCE_WINDOW: Duration = Duration::from_millis(1000);
+
+/// Everything about the expressions an edit can change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub storage: HashMap<ExpressionId, String>,
+    pub order: Vec<ExpressionId>,
+    pub styles: HashMap<ExpressionId, Style>,
+    pub hidden: HashSet<ExpressionId>,
+    pub domains: HashMap<ExpressionId, Domain>,
+    pub sliders: HashMap<ExpressionId, Slider>,
+}
+
+/// Edits that get merged with the previous one when they happen in quick succession.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    Text(ExpressionId),
+    Style(ExpressionId),
+    Domain(ExpressionId),
+    Slider(ExpressionId),
+}
+
+#[derive(Debug, Default)]
+pub struct History {
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    last_edit: Option<(Edit, Instant)>,
+}
+
+impl History {
+    /// Records the state from before an edit. Rapid edits of the same `kind` keep the state from
+    /// before the first one.
+    pub fn record(&mut self, before: Snapshot, kind: Option<Edit>) {
+        let now = Instant::now();
+        let coalesce = matches!(
+            (kind, self.last_edit),
+            (Some(kind), Some((last, at))) if kind == last && now - at < COALESCE_WINDOW
+        );
+        self.last_edit = kind.map(|kind| (kind, now));
+
+        if !coalesce {
+            self.undo.push(before);
+        }
+        self.redo.clear();
+    }
+
+    pub fn undo(&mut self, current: Snapshot) -> Option<Snapshot> {
+        let previous = self.undo.pop()?;
+        self.redo.push(current);
+        self.last_edit = None;
+        Some(previous)
+    }
+
+    pub fn redo(&mut self, current: Snapshot) -> Option<Snapshot> {
+        let next = self.redo.pop()?;
+        self.undo.push(current);
+        self.last_edit = None;
+        Some(next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: ExpressionId = ExpressionId(0);
+
+    fn snapshot(latex: &str) -> Snapshot {
+        Snapshot {
+            storage: HashMap::from([(ID, latex.to_string())]),
+            order: vec![ID],
+            styles: HashMap::new(),
+            hidden: HashSet::new(),
+            domains: HashMap::new(),
+            sliders: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn rapid_edits_are_undone_at_once() {
+        let mut history = History::default();
+        history.record(snapshot(""), Some(Edit::Text(ID)));
+        history.record(snapshot("a"), Some(Edit::Text(ID)));
+        history.record(snapshot("a="), Some(Edit::Text(ID)));
+        assert_eq!(history.undo(snapshot("a=1")), Some(snapshot("")));
+        assert_eq!(history.undo(snapshot("")), None);
+    }
+
+    #[test]
+    fn edits_after_the_window_are_undone_separately() {
+        let mut history = History::default();
+        history.record(snapshot(""), Some(Edit::Text(ID)));
+        history.last_edit = Some((Edit::Text(ID), Instant::now() - COALESCE_WINDOW * 2));
+        history.record(snapshot("a"), Some(Edit::Text(ID)));
+        assert_eq!(history.undo(snapshot("a=")), Some(snapshot("a")));
+        assert_eq!(history.undo(snapshot("a")), Some(snapshot("")));
+    }
+
+    #[test]
+    fn different_edits_are_undone_separately() {
+        let mut history = History::default();
+        history.record(snapshot(""), Some(Edit::Text(ID)));
+        history.record(snapshot("a"), Some(Edit::Style(ID)));
+        history.record(snapshot("a"), None);
+        history.record(snapshot("b"), None);
+        assert_eq!(history.undo(snapshot("c")), Some(snapshot("b")));
+        assert_eq!(history.undo(snapshot("b")), Some(snapshot("a")));
+        assert_eq!(history.undo(snapshot("a")), Some(snapshot("a")));
+        assert_eq!(history.undo(snapshot("a")), Some(snapshot("")));
+    }
+
+    #[test]
+    fn redo_restores_what_was_undone() {
+        let mut history = History::default();
+        history.record(snapshot(""), Some(Edit::Text(ID)));
+        assert_eq!(history.undo(snapshot("a")), Some(snapshot("")));
+        assert_eq!(history.redo(snapshot("")), Some(snapshot("a")));
+        assert_eq!(history.redo(snapshot("a")), None);
+        assert_eq!(history.undo(snapshot("a")), Some(snapshot("")));
+    }
+
+    #[test]
+    fn new_edits_drop_redo() {
+        let mut history = History::default();
+        history.record(snapshot(""), Some(Edit::Text(ID)));
+        history.undo(snapshot("a"));
+        history.record(snapshot(""), Some(Edit::Domain(ID)));
+        assert_eq!(history.redo(snapshot("b")), None);
+    }
+
+    #[test]
+    fn undo_ends_coalescing() {
+        let mut history = History::default();
+        history.record(snapshot(""), Some(Edit::Text(ID)));
+        history.record(snapshot("a"), Some(Edit::Text(ID)));
+        history.undo(snapshot("ab"));
+        history.record(snapshot(""), Some(Edit::Text(ID)));
+        history.record(snapshot("c"), Some(Edit::Text(ID)));
+        assert_eq!(history.undo(snapshot("cd")), Some(snapshot("")));
+        assert_eq!(history.undo(snapshot("")), None);
+    }
+}
@@ -0,0 +1,227 @@
+use reqwest::header::ACCEPT;
+use serde_json::Value;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Error)]
+pub enum LoadError {
+    #[error("failed to reach {url}: {message}")]
+    Network { url: String, message: String },
+    #[error("{url} responded with {status}")]
+    Status { url: String, status: u16 },
+    #[error("{url} did not respond with JSON: {message}")]
+    Json { url: String, message: String },
+    #[error("{url} is not a Desmos graph: {message}")]
+    Shape { url: String, message: String },
+}
+
+/// Turns what gets passed to `--url` into the url of a graph state.
+#[derive(Debug, Clone)]
+pub struct DesmosResolver {
+    /// Where share links are resolved, so a local server can stand in for desmos.com.
+    base_url: String,
+}
+
+impl Default for DesmosResolver {
+    fn default() -> Self {
+        Self::new("https://www.desmos.com")
+    }
+}
+
+impl DesmosResolver {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Resolves calculator share links and bare graph hashes, leaving any other url alone.
+    pub fn resolve(&self, input: &str) -> String {
+        match share_hash(input) {
+            Some(hash) => format!("{}/calculator/{hash}", self.base_url),
+            None => input.trim().to_string(),
+        }
+    }
+
+    /// Fetches the graph state `input` refers to.
+    pub async fn load(self, input: String) -> Result<Value, LoadError> {
+        get_url(self.resolve(&input)).await.map(unwrap_state)
+    }
+}
+
+/// The hash of a `desmos.com/calculator/<hash>` link, or of a bare hash.
+fn share_hash(input: &str) -> Option<&str> {
+    let input = input.trim();
+    // Only input without a scheme can be a bare hash, so `http://localhost` stays a url.
+    let (has_scheme, input) = match input.split_once("://") {
+        Some((_, rest)) => (true, rest),
+        None => (false, input),
+    };
+    let path = input.split(['?', '#']).next()?;
+    if !has_scheme && is_hash(path) {
+        return Some(path);
+    }
+
+    let (host, path) = path.split_once('/')?;
+    if host.strip_prefix("www.").unwrap_or(host) != "desmos.com" {
+        return None;
+    }
+    let hash = path.trim_end_matches('/').strip_prefix("calculator/")?;
+    is_hash(hash).then_some(hash)
+}
+
+fn is_hash(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Desmos wraps the graph state in the graph's metadata, while saved states are bare.
+fn unwrap_state(mut value: Value) -> Value {
+    if value.get("state").is_some_and(Value::is_object) {
+        value["state"].take()
+    } else {
+        value
+    }
+}
+
+/// Fetches the json at `url`.
+async fn get_url(url: String) -> Result<Value, LoadError> {
+    let network = |e: reqwest::Error| LoadError::Network {
+        url: url.clone(),
+        message: e.to_string(),
+    };
+
+    let res = reqwest::Client::new()
+        .get(&url)
+        .header(ACCEPT, "application/json")
+        .send()
+        .await
+        .map_err(network)?;
+
+    let status = res.status();
+    if !status.is_success() {
+        return Err(LoadError::Status {
+            url: url.clone(),
+            status: status.as_u16(),
+        });
+    }
+
+    let text = res.text().await.map_err(network)?;
+
+    serde_json::from_str(&text).map_err(|e| LoadError::Json {
+        url: url.clone(),
+        message: e.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+
+    #[test]
+    fn share_links() {
+        for link in [
+            "https://www.desmos.com/calculator/abc123",
+            "https://desmos.com/calculator/abc123",
+            "http://www.desmos.com/calculator/abc123/",
+            "www.desmos.com/calculator/abc123",
+            "desmos.com/calculator/abc123?lang=en",
+            "https://www.desmos.com/calculator/abc123#top",
+        ] {
+            assert_eq!(share_hash(link), Some("abc123"), "{link}");
+        }
+    }
+
+    #[test]
+    fn bare_hashes() {
+        assert_eq!(share_hash("abc123"), Some("abc123"));
+        assert_eq!(share_hash("  abc123\n"), Some("abc123"));
+        assert_eq!(share_hash(""), None);
+    }
+
+    #[test]
+    fn other_urls() {
+        for url in [
+            "https://example.com/calculator/abc123",
+            "https://www.desmos.com/geometry/abc123",
+            "https://www.desmos.com/calculator/abc-123",
+            "https://www.desmos.com/calculator",
+            "http://localhost",
+            "https://example",
+            "example.com",
+        ] {
+            assert_eq!(share_hash(url), None, "{url}");
+        }
+    }
+
+    #[test]
+    fn resolve() {
+        let resolver = DesmosResolver::new("http://127.0.0.1:8000/");
+        assert_eq!(
+            resolver.resolve("https://www.desmos.com/calculator/abc123"),
+            "http://127.0.0.1:8000/calculator/abc123"
+        );
+        assert_eq!(
+            resolver.resolve("abc123"),
+            "http://127.0.0.1:8000/calculator/abc123"
+        );
+        assert_eq!(
+            resolver.resolve(" https://example.com/graph.json "),
+            "https://example.com/graph.json"
+        );
+    }
+
+    /// Serves a single request with `body`, returning the address and the request line it got.
+    fn stub_server(
+        status: &'static str,
+        body: &'static str,
+    ) -> (String, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 4096];
+            let read = stream.read(&mut request).unwrap();
+            let request = String::from_utf8_lossy(&request[..read]).to_string();
+            write!(
+                stream,
+                "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+            request.lines().next().unwrap_or_default().to_string()
+        });
+        (address, handle)
+    }
+
+    fn block_on<T>(future: impl std::future::Future<Output = T>) -> T {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn load_from_stub_server() {
+        let (address, server) = stub_server(
+            "200 OK",
+            r#"{"hash": "abc123", "state": {"expressions": {"list": []}}}"#,
+        );
+        let state = block_on(DesmosResolver::new(address).load("abc123".to_string())).unwrap();
+        assert_eq!(state, serde_json::json!({"expressions": {"list": []}}));
+        assert_eq!(server.join().unwrap(), "GET /calculator/abc123 HTTP/1.1");
+    }
+
+    #[test]
+    fn load_reports_status() {
+        let (address, server) = stub_server("404 Not Found", "{}");
+        let error = block_on(DesmosResolver::new(address).load("abc123".to_string())).unwrap_err();
+        assert!(matches!(error, LoadError::Status { status: 404, .. }));
+        server.join().unwrap();
+    }
+}
@@ -1,34 +1,57 @@
-use std::collections::HashMap;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
 
-use components::sidebar;
-use desmoxide::{
-    graph::expressions::{CompiledEquations, ExpressionId, Expressions},
-    interop::{Expression, Graph},
+use components::{
+    banner::{self, Banner},
+    settings::{self, Bounds},
+    sidebar::{self, Drag},
+};
+use desmoxide::graph::expressions::{CompiledEquations, ExpressionId, Expressions};
+use document::Document;
+use file::FileError;
+use graph::{
+    Comparison, Domain, GraphRenderer, GraphSettings, GraphView, PlotKind, Rewrite, Trace, Viewport,
 };
-use graph::GraphRenderer;
+use history::{Edit, History, Snapshot};
 use iced::{
     alignment::Horizontal,
-    overlay,
+    keyboard, mouse, overlay,
+    time::{self, Duration, Instant},
     widget::{
         self,
         canvas::Cache,
-        container, mouse_area, opaque,
+        column, container, mouse_area, opaque,
         pane_grid::{self, Axis, Content, Pane, ResizeEvent},
         row,
         text_input::{self, focus, Id},
         Canvas, Stack, TextInput,
     },
-    Application, Color, Length, Padding, Settings, Task, Vector,
+    window, Application, Color, Element, Length, Padding, Settings, Size, Subscription, Task,
+    Vector,
 };
+use loader::{DesmosResolver, LoadError};
+use style::Style;
+use variables::{Animation, DependencyGraph, Slider};
 
 use clap::Parser;
 
-use reqwest::header::{ACCEPT, CONTENT_TYPE};
 use wasm_bindgen::prelude::wasm_bindgen;
 use wasm_bindgen::JsValue;
 
 mod components;
+mod document;
+mod file;
 mod graph;
+mod history;
+mod loader;
+mod style;
+mod variables;
+
+/// How often playing sliders move.
+const FRAME: Duration = Duration::from_millis(16);
 
 static DCG_FONT: &[u8; 45324] = include_bytes!("./dcg-icons-2024-08-02.ttf");
 
@@ -39,6 +62,7 @@ pub fn start() -> Result<(), JsValue> {
     iced::application("Somsed", Somsed::update, Somsed::view)
         .font(DCG_FONT)
         .antialiasing(true)
+        .subscription(Somsed::subscription)
         .run()
         .map_err(|e| JsValue::from_str(&e.to_string()))
 }
@@ -48,46 +72,122 @@ fn main() -> iced::Result {
     iced::application("Somsed", Somsed::update, Somsed::view)
         .font(DCG_FONT)
         .antialiasing(true)
-        .run_with(move || (Somsed::new(options), Task::none()))
+        .subscription(Somsed::subscription)
+        .run_with(move || Somsed::new(options))
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     Moved(Vector),
-    Scaled(f32, Option<Vector>),
+    Scaled(Vector, Option<Vector>),
     EquationChanged(ExpressionId, String),
     EquationAdded(String),
+    EquationRemoved(ExpressionId),
+    DomainChanged(ExpressionId, Domain),
+    SliderMoved(ExpressionId, f32),
+    SliderChanged(ExpressionId, Slider),
+    TogglePlaying(ExpressionId),
+    Tick(Instant),
+    StyleChanged(ExpressionId, Style),
+    EditStyle(Option<ExpressionId>),
+    ToggleHidden(ExpressionId),
+    DragStart(ExpressionId),
+    DragHover(ExpressionId),
+    DragDrop,
+    DragCancel,
+    Undo,
+    Redo,
+    Open,
+    Load(String),
+    Loaded(String, Result<serde_json::Value, LoadError>),
+    DismissBanner,
+    Opened(Result<(PathBuf, serde_json::Value), FileError>),
+    Save,
+    Saved(Result<PathBuf, FileError>),
     ShowError(Option<ExpressionId>),
     FocusExpr(usize),
     Resized(pane_grid::ResizeEvent),
+    GraphResized(Size),
+    WindowResized(Size),
+    SettingsChanged(GraphSettings),
+    BoundsChanged(Bounds),
+    BoundsSubmitted,
+    Hover(Option<Trace>),
+    Pin(Option<Trace>),
+}
+
+/// What is known about each expression besides its latex, as shown in the sidebar and graph.
+#[derive(Default)]
+pub struct ExpressionState {
+    pub errors: HashMap<ExpressionId, String>,
+    /// Errors from evaluating expressions that compiled, found while drawing them.
+    pub draw_errors: RefCell<HashMap<ExpressionId, String>>,
+    pub domains: HashMap<ExpressionId, Domain>,
+    pub sliders: HashMap<ExpressionId, Slider>,
+    /// Sliders that are playing.
+    pub animations: HashMap<ExpressionId, Animation>,
+    pub kinds: HashMap<ExpressionId, PlotKind>,
+    pub inequalities: HashMap<ExpressionId, Comparison>,
+    pub styles: HashMap<ExpressionId, Style>,
+    pub hidden: HashSet<ExpressionId>,
 }
 
 enum PaneType {
     Graph,
     Sidebar,
+    Settings,
 }
 
 struct Somsed {
     panes: pane_grid::State<PaneType>,
     graph_caches: HashMap<ExpressionId, Cache>,
-    errors: HashMap<ExpressionId, String>,
     expressions: Expressions,
+    dependencies: DependencyGraph,
 
     compiled_eqs: CompiledEquations,
+    /// The order expressions are listed in, which `expressions` doesn't keep track of.
+    order: Vec<ExpressionId>,
+    expr_state: ExpressionState,
+    /// When playing sliders last moved.
+    last_tick: Option<Instant>,
 
     shown_error: Option<ExpressionId>,
-
-    scale: f32,
-    mid: Vector,
-    resolution: u32,
+    style_editor: Option<ExpressionId>,
+    drag: Option<Drag>,
+    history: History,
+
+    /// The size of the window, which the size of the graph pane follows from.
+    window_size: Option<Size>,
+
+    /// The graph state last opened, saved back out with the current expressions.
+    graph_state: serde_json::Value,
+    file: Option<PathBuf>,
+    banner: Option<Banner>,
+    resolver: DesmosResolver,
+
+    graph_view: GraphView,
+    /// A viewport from a loaded graph and whether its axes are kept square, waiting for the size
+    /// of the graph pane to be known.
+    viewport: Option<(Viewport, bool)>,
+    settings: GraphSettings,
+    /// Bounds typed into the settings pane that haven't been applied yet.
+    bounds_input: Option<Bounds>,
 }
 
 #[derive(Parser, Debug, Default)]
 #[command(version, about, long_about = None)]
 struct Options {
-    /// url of the program to fetch
+    /// url of the program to fetch, a desmos.com share link, or a graph hash
     #[arg(short, long)]
     url: Option<String>,
+
+    /// server share links and graph hashes are resolved against
+    #[arg(long, default_value = "https://www.desmos.com")]
+    desmos: String,
+
+    /// graph file to open, and to save to with ctrl+s
+    #[arg(short, long, conflicts_with = "url")]
+    file: Option<PathBuf>,
 }
 
 impl Default for Somsed {
@@ -97,101 +197,418 @@ impl Default for Somsed {
         let (mut panes, pane) = pane_grid::State::new(PaneType::Sidebar);
 
         panes.split(Axis::Vertical, pane, PaneType::Graph);
+        if let Some((_, split)) = panes.split(Axis::Horizontal, pane, PaneType::Settings) {
+            panes.resize(split, 0.7);
+        }
 
         Self {
             panes,
-            errors: HashMap::new(),
             compiled_eqs: CompiledEquations::default(),
-            scale: 100.0,
-            mid: Vector { x: 0.0, y: 0.0 },
-            resolution: 1000,
+            order: Vec::new(),
+            expr_state: ExpressionState::default(),
+            last_tick: None,
+            graph_view: GraphView::default(),
+            viewport: None,
+            settings: GraphSettings::default(),
+            bounds_input: None,
 
             graph_caches: HashMap::new(),
             expressions,
+            dependencies: DependencyGraph::default(),
 
             shown_error: None,
+            style_editor: None,
+            drag: None,
+            history: History::default(),
+
+            window_size: None,
+
+            graph_state: serde_json::Value::Null,
+            file: None,
+            banner: None,
+            resolver: DesmosResolver::default(),
         }
     }
 }
 
 impl Somsed {
-    async fn get_url(url: &str) -> Graph {
-        let res = reqwest::Client::new()
-            .get(url)
-            .header(ACCEPT, "application/json")
-            .send()
-            .await
-            .expect("Should send the request");
-
-        let text = res.text().await.expect("Should get response text");
-
-        serde_json::from_str(&text).expect("failed to deserealize graph")
-    }
-    fn new(options: Options) -> Self {
-        let mut graph_caches = HashMap::new();
-        let mut expressions = Expressions::new(if let Some(url) = options.url {
-            let graph = futures_lite::future::block_on(Self::get_url(&url));
-            graph
-                .exprs()
-                .into_iter()
-                .filter_map(|expr| match expr {
-                    Expression::Expression { id, latex, .. } => latex.as_ref().map(|latex| {
-                        graph_caches.insert(ExpressionId(*id), Cache::new());
-                        (ExpressionId(*id), latex.clone())
-                    }),
-                    _ => None,
-                })
-                .collect()
-        } else {
-            HashMap::new()
-        });
+    fn new(options: Options) -> (Self, Task<Message>) {
+        let mut somsed = Self {
+            resolver: DesmosResolver::new(options.desmos),
+            ..Self::default()
+        };
+        if let Some(url) = options.url {
+            let task = somsed.fetch(url);
+            return (somsed, task);
+        } else if let Some(path) = options.file {
+            // A file that doesn't exist yet is created on the first save.
+            if path.exists() {
+                let message = match file::read(path.clone()).map(Document::from_json) {
+                    Ok(Ok(document)) => {
+                        somsed.load(document);
+                        None
+                    }
+                    Ok(Err(e)) => Some(format!("{} is not a valid graph: {}", path.display(), e)),
+                    Err(e) => Some(e.to_string()),
+                };
+                somsed.banner = message.map(|message| Banner::Error {
+                    message,
+                    retry: None,
+                });
+            }
+            somsed.file = Some(path);
+        }
+        (somsed, Task::none())
+    }
 
-        expressions.parse_all();
+    fn fetch(&mut self, url: String) -> Task<Message> {
+        self.banner = Some(Banner::Loading(url.clone()));
+        Task::perform(self.resolver.clone().load(url.clone()), move |state| {
+            Message::Loaded(url.clone(), state)
+        })
+    }
 
-        let mut errors = HashMap::new();
-        let compiled_eqs = expressions.compile_all(&mut errors);
+    /// Replaces every expression with the ones from `document`.
+    fn load(&mut self, document: Document) {
+        self.order = document.expressions.iter().map(|(i, _)| *i).collect();
+        self.graph_caches = self.order.iter().map(|i| (*i, Cache::new())).collect();
+        self.expressions = Expressions::new(document.expressions.into_iter().collect());
+        self.expr_state.domains = document.domains;
+        self.expr_state.sliders = document.sliders;
+        self.expr_state.animations.clear();
+        self.expr_state.styles = document.styles;
+        self.expr_state.hidden = document.hidden;
+        self.viewport = document.viewport.map(|v| (v, document.square_axes));
+        self.settings = document.settings;
+        self.bounds_input = None;
+        self.graph_view.samples.borrow_mut().clear();
+        self.graph_view.analysis.borrow_mut().clear();
+        self.graph_view.hover = None;
+        self.graph_view.pinned = None;
+        self.graph_state = document.state;
+
+        self.history = History::default();
+        self.style_editor = None;
+        self.shown_error = None;
+        self.compile();
+        self.fit_viewport();
+    }
 
-        let (mut panes, pane) = pane_grid::State::new(PaneType::Sidebar);
+    /// Works out the size of the graph pane from the window's. The canvas only reports its size
+    /// once it gets input, and a loaded viewport shouldn't wait for the cursor to move.
+    fn resize_graph(&mut self) {
+        let Some(window) = self.window_size else {
+            return;
+        };
+        let regions = self.panes.layout().pane_regions(0.0, window);
+        let size = self
+            .panes
+            .iter()
+            .find(|(_, kind)| matches!(kind, PaneType::Graph))
+            .and_then(|(pane, _)| regions.get(pane))
+            .map(|region| region.size());
+        if let Some(size) = size {
+            self.graph_view.size = size;
+            self.fit_viewport();
+        }
+    }
 
-        panes.split(Axis::Vertical, pane, PaneType::Graph);
+    /// Moves to the viewport of the loaded graph, once there is a pane to fit it into.
+    fn fit_viewport(&mut self) {
+        let fit = self.viewport.and_then(|(v, square_axes)| {
+            v.fit(self.graph_view.size, square_axes, self.settings.axes)
+        });
+        if let Some((scale, mid)) = fit {
+            self.graph_view.scale = scale;
+            self.graph_view.mid = mid;
+            self.viewport = None;
+            self.clear_caches();
+        }
+    }
 
-        Self {
-            panes,
-            errors,
-            compiled_eqs,
-            scale: 100.0,
-            mid: Vector { x: 0.0, y: 0.0 },
-            resolution: 1000,
+    fn document(&self) -> Document {
+        Document {
+            expressions: self
+                .order
+                .iter()
+                .filter_map(|i| Some((*i, self.expressions.storage.get(i)?.clone())))
+                .collect(),
+            domains: self.expr_state.domains.clone(),
+            sliders: self.expr_state.sliders.clone(),
+            styles: self.expr_state.styles.clone(),
+            hidden: self.expr_state.hidden.clone(),
+            viewport: match self.viewport {
+                Some((viewport, _)) => Some(viewport),
+                None => Viewport::visible(
+                    self.graph_view.scale,
+                    self.graph_view.mid,
+                    self.graph_view.size,
+                    self.settings.axes,
+                ),
+            },
+            square_axes: match self.viewport {
+                Some((_, square_axes)) => square_axes,
+                None => self.graph_view.scale.x == self.graph_view.scale.y,
+            },
+            settings: self.settings.clone(),
+            state: self.graph_state.clone(),
+        }
+    }
 
-            expressions,
-            graph_caches,
+    fn compile(&mut self) {
+        self.dependencies = DependencyGraph::new(&self.expressions.storage);
+        self.expr_state.errors.clear();
+        self.compiled_eqs = CompiledEquations::default();
+        self.expr_state.kinds.clear();
+        self.expr_state.inequalities.clear();
+        let ids: HashSet<ExpressionId> = self.expressions.storage.keys().copied().collect();
+        self.recompile(&ids);
+    }
 
-            shown_error: None,
+    /// Compiles `ids` again, along with just the definitions they use, keeping everything else
+    /// as it was.
+    fn recompile(&mut self, ids: &HashSet<ExpressionId>) {
+        let mut rewrites = HashMap::new();
+        let mut source = Expressions::new(
+            self.dependencies
+                .dependencies(ids.iter().copied())
+                .into_iter()
+                .filter_map(|i| Some((i, self.expressions.storage.get(&i)?)))
+                .map(|(i, latex)| match graph::rewrite(latex) {
+                    Some((body, rewrite)) => {
+                        rewrites.insert(i, rewrite);
+                        (i, body)
+                    }
+                    None => (i, latex.clone()),
+                })
+                .collect(),
+        );
+        let mut errors = source.parse_all();
+        let compiled = source.compile_all(&mut errors);
+
+        for i in ids {
+            self.expr_state.errors.remove(i);
+            self.expr_state.draw_errors.borrow_mut().remove(i);
+            self.compiled_eqs.compiled_equations.remove(i);
+            self.expr_state.kinds.remove(i);
+            self.expr_state.inequalities.remove(i);
+            if let Some(error) = errors.remove(i) {
+                self.expr_state.errors.insert(*i, error);
+            }
+            // Only expressions desmoxide accepted get a slider, so the scanner in `variables`
+            // never offers one for something desmoxide reads differently.
+            let assignment = self
+                .expressions
+                .storage
+                .get(i)
+                .filter(|_| !self.expr_state.errors.contains_key(i))
+                .and_then(|latex| variables::number_assignment(latex));
+            if let Some((_, value)) = assignment {
+                self.expr_state
+                    .sliders
+                    .entry(*i)
+                    .or_insert_with(|| Slider::around(value));
+            }
+        }
+
+        for (i, eq) in compiled.compiled_equations {
+            if !ids.contains(&i) {
+                continue;
+            }
+            match graph::plot_kind(graph::segment(&eq)) {
+                Ok(kind) => {
+                    let kind = match (kind, rewrites.get(&i)) {
+                        (PlotKind::Function, Some(Rewrite::Polar)) => PlotKind::Polar,
+                        (
+                            PlotKind::Function | PlotKind::Relation,
+                            Some(Rewrite::Inequality(inequality)),
+                        ) => {
+                            self.expr_state
+                                .inequalities
+                                .insert(i, inequality.comparison);
+                            inequality.kind
+                        }
+                        (kind, _) => kind,
+                    };
+                    match kind {
+                        PlotKind::Parametric => {
+                            self.expr_state.domains.entry(i).or_default();
+                        }
+                        PlotKind::Polar => {
+                            self.expr_state
+                                .domains
+                                .entry(i)
+                                .or_insert_with(Domain::polar);
+                        }
+                        PlotKind::Function | PlotKind::Relation => (),
+                    }
+                    self.expr_state.kinds.insert(i, kind);
+                    self.check_domain(i);
+                }
+                Err(e) => {
+                    self.expr_state.errors.insert(i, e.to_string());
+                }
+            }
+            self.compiled_eqs.compiled_equations.insert(i, eq);
         }
     }
 
-    fn view(&self) -> pane_grid::PaneGrid<'_, Message> {
-        pane_grid::PaneGrid::new(&self.panes, move |_, id, _| match id {
+    /// Reports a domain that doesn't parse as soon as it is typed, instead of once the graph has
+    /// been drawn and the sidebar happens to be rebuilt.
+    fn check_domain(&self, i: ExpressionId) {
+        let swept = self
+            .expr_state
+            .kinds
+            .get(&i)
+            .copied()
+            .and_then(PlotKind::parameter);
+        let error = swept
+            .and(self.expr_state.domains.get(&i))
+            .and_then(|domain| domain.bounds().err());
+        let mut errors = self.expr_state.draw_errors.borrow_mut();
+        match error {
+            Some(error) => errors.insert(i, error.to_string()),
+            None => errors.remove(&i),
+        };
+    }
+
+    fn view(&self) -> Element<'_, Message> {
+        let panes = pane_grid::PaneGrid::new(&self.panes, move |_, id, _| match id {
             PaneType::Graph => Content::new(
                 Canvas::new(GraphRenderer::new(
                     &self.compiled_eqs,
                     &self.graph_caches,
-                    self.scale,
-                    self.mid,
-                    self.resolution,
+                    &self.order,
+                    &self.expr_state,
+                    &self.settings,
+                    &self.graph_view,
                 ))
                 .width(Length::Fill)
                 .height(Length::Fill),
             ),
             PaneType::Sidebar => pane_grid::Content::new(sidebar::view(
                 &self.expressions.storage,
-                &self.errors,
+                &self.order,
+                &self.expr_state,
                 &self.shown_error,
+                &self.style_editor,
+                &self.drag,
             )),
+            PaneType::Settings => Content::new(settings::view(&self.settings, &self.bounds())),
         })
         .on_resize(10, Message::Resized)
         .width(Length::Fill)
-        .height(Length::Fill)
+        .height(Length::Fill);
+
+        match &self.banner {
+            Some(b) => column![banner::view(b), panes].into(),
+            None => panes.into(),
+        }
+    }
+
+    /// The bounds shown in the settings pane, which follow the view unless they are being edited.
+    fn bounds(&self) -> Bounds {
+        self.bounds_input.clone().unwrap_or_else(|| {
+            Viewport::visible(
+                self.graph_view.scale,
+                self.graph_view.mid,
+                self.graph_view.size,
+                self.settings.axes,
+            )
+            .map(|viewport| Bounds::new(viewport, self.settings.axes))
+            .unwrap_or_default()
+        })
+    }
+
+    /// Applies new graph settings, keeping the same region in view when an axis changes scale.
+    fn change_settings(&mut self, settings: GraphSettings) {
+        let visible = Viewport::visible(
+            self.graph_view.scale,
+            self.graph_view.mid,
+            self.graph_view.size,
+            self.settings.axes,
+        );
+        let axes_changed = settings.axes != self.settings.axes;
+        self.settings = settings;
+        if !axes_changed {
+            return;
+        }
+
+        // Crossings are kept in view coordinates, which a new axis scale changes.
+        self.graph_view.analysis.borrow_mut().clear();
+        let axes = self.settings.axes;
+        if let Some((scale, mid)) =
+            visible.and_then(|v| v.clamp_to(axes).fit(self.graph_view.size, false, axes))
+        {
+            self.graph_view.scale = scale;
+            self.graph_view.mid = mid;
+        }
+        self.bounds_input = None;
+        self.clear_caches();
+    }
+
+    /// Sets the variable of the slider `i` to `value`, adding what that changes to `affected`
+    /// without recompiling it. Returns false if `i` isn't a slider.
+    fn set_slider(
+        &mut self,
+        i: ExpressionId,
+        value: f32,
+        affected: &mut HashSet<ExpressionId>,
+    ) -> bool {
+        let Some((name, _)) = self
+            .expressions
+            .storage
+            .get(&i)
+            .and_then(|latex| variables::number_assignment(latex))
+        else {
+            return false;
+        };
+        let slider = self.expr_state.sliders.get(&i).cloned().unwrap_or_default();
+        let latex = format!("{name}={}", slider.format(value));
+        // Playing sliders move less than a step most frames, which changes nothing.
+        if self.expressions.storage.get(&i) != Some(&latex) {
+            affected.extend(self.replace_equation(i, latex));
+        }
+        true
+    }
+
+    /// Changes the latex of `i`, recompiling and redrawing it and everything that depends on it.
+    fn set_equation(&mut self, i: ExpressionId, latex: String) {
+        let affected = self.replace_equation(i, latex);
+        self.refresh(&affected);
+    }
+
+    /// Changes the latex of `i` without recompiling anything, returning what needs refreshing.
+    fn replace_equation(&mut self, i: ExpressionId, latex: String) -> HashSet<ExpressionId> {
+        // What used the old definition changes as well as what uses the new one, along with other
+        // definitions of either name, which were or now are in conflict with this one.
+        let mut affected = self.dependencies.dependents(self.dependencies.same_name(i));
+        self.dependencies.update(i, &latex);
+        self.expressions.set_equation(i, latex);
+        affected.extend(self.dependencies.dependents(self.dependencies.same_name(i)));
+        affected
+    }
+
+    /// Recompiles `ids` and drops what was drawn and traced of them.
+    fn refresh(&mut self, ids: &HashSet<ExpressionId>) {
+        self.recompile(ids);
+        for i in ids {
+            if let Some(cache) = self.graph_caches.get(i) {
+                cache.clear();
+            }
+            self.graph_view.analysis.borrow_mut().remove(*i);
+            self.clear_traces(*i);
+        }
+    }
+
+    /// Drops traced points on `id`, which has moved or gone away.
+    fn clear_traces(&mut self, id: ExpressionId) {
+        if self.graph_view.hover.is_some_and(|trace| trace.id == id) {
+            self.graph_view.hover = None;
+        }
+        if self.graph_view.pinned.is_some_and(|trace| trace.id == id) {
+            self.graph_view.pinned = None;
+        }
     }
 
     pub fn clear_caches(&mut self) {
@@ -200,33 +617,334 @@ impl Somsed {
         }
     }
 
+    /// Moves `id` to where `over` is in the list, shifting everything in between.
+    fn move_expression(&mut self, id: ExpressionId, over: ExpressionId) {
+        let (Some(from), Some(to)) = (
+            self.order.iter().position(|i| *i == id),
+            self.order.iter().position(|i| *i == over),
+        ) else {
+            return;
+        };
+        if from == to {
+            return;
+        }
+        let moved = self.order.remove(from);
+        self.order.insert(to, moved);
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            storage: self.expressions.storage.clone(),
+            order: self.order.clone(),
+            styles: self.expr_state.styles.clone(),
+            hidden: self.expr_state.hidden.clone(),
+            domains: self.expr_state.domains.clone(),
+            sliders: self.expr_state.sliders.clone(),
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.expressions.storage = snapshot.storage;
+        self.order = snapshot.order;
+        self.expr_state.styles = snapshot.styles;
+        self.expr_state.hidden = snapshot.hidden;
+        self.expr_state.domains = snapshot.domains;
+        self.expr_state.sliders = snapshot.sliders;
+
+        self.graph_caches
+            .retain(|i, _| self.expressions.storage.contains_key(i));
+        for i in self.expressions.storage.keys() {
+            self.graph_caches.entry(*i).or_insert_with(Cache::new);
+        }
+        self.compile();
+        self.clear_caches();
+        self.graph_view.analysis.borrow_mut().clear();
+        self.graph_view.hover = None;
+        self.graph_view.pinned = None;
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        let keys = iced::event::listen_with(|event, _, _| match event {
+            iced::Event::Keyboard(keyboard::Event::KeyPressed {
+                key: keyboard::Key::Character(c),
+                modifiers,
+                ..
+            }) if modifiers.command() => match c.as_str() {
+                "z" | "Z" if modifiers.shift() => Some(Message::Redo),
+                "z" | "Z" => Some(Message::Undo),
+                "y" | "Y" => Some(Message::Redo),
+                "o" | "O" => Some(Message::Open),
+                "s" | "S" => Some(Message::Save),
+                _ => None,
+            },
+            _ => None,
+        });
+        let window = iced::event::listen_with(|event, _, _| match event {
+            iced::Event::Window(
+                window::Event::Opened { size, .. } | window::Event::Resized(size),
+            ) => Some(Message::WindowResized(size)),
+            _ => None,
+        });
+        let mut subscriptions = vec![keys, window];
+        // A drag ends wherever the mouse is released, and is cancelled if the cursor leaves the
+        // window.
+        if self.drag.is_some() {
+            subscriptions.push(iced::event::listen_with(|event, _, _| match event {
+                iced::Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                    Some(Message::DragDrop)
+                }
+                iced::Event::Mouse(mouse::Event::CursorLeft) => Some(Message::DragCancel),
+                _ => None,
+            }));
+        }
+        if !self.expr_state.animations.is_empty() {
+            subscriptions.push(time::every(FRAME).map(Message::Tick));
+        }
+        Subscription::batch(subscriptions)
+    }
+
     fn update(&mut self, message: Message) -> Task<Message> {
+        // `None` for messages that don't edit expressions, and `Some(None)` for edits that are
+        // never merged with the previous one.
+        let edit = match &message {
+            Message::EquationChanged(i, _) => Some(Some(Edit::Text(*i))),
+            Message::StyleChanged(i, _) => Some(Some(Edit::Style(*i))),
+            Message::DomainChanged(i, _) => Some(Some(Edit::Domain(*i))),
+            // Dragging a slider edits the text of its expression.
+            Message::SliderMoved(i, _) => Some(Some(Edit::Text(*i))),
+            Message::SliderChanged(i, _) => Some(Some(Edit::Slider(*i))),
+            Message::EquationAdded(_)
+            | Message::EquationRemoved(_)
+            | Message::ToggleHidden(_)
+            | Message::DragDrop => Some(None),
+            _ => None,
+        };
+        let Some(kind) = edit else {
+            return self.apply(message);
+        };
+
+        let before = self.snapshot();
+        let task = self.apply(message);
+        if before != self.snapshot() {
+            self.history.record(before, kind);
+        }
+        task
+    }
+
+    fn apply(&mut self, message: Message) -> Task<Message> {
         match message {
             Message::Moved(p) => {
-                self.mid = self.mid + p;
+                self.graph_view.mid = self.graph_view.mid + p;
                 self.clear_caches();
             }
-            Message::EquationChanged(i, s) => {
-                self.expressions.set_equation(i, s);
-                self.errors = self.expressions.parse_all();
-
-                self.compiled_eqs = self.expressions.compile_all(&mut self.errors);
-
-                self.graph_caches[&i].clear();
-            }
+            Message::EquationChanged(i, s) => self.set_equation(i, s),
             Message::EquationAdded(s) => {
                 self.expressions.add_equation(s);
 
-                self.graph_caches
-                    .insert(ExpressionId(self.expressions.max_id - 1), Cache::new());
-                self.errors = self.expressions.parse_all();
-                self.compiled_eqs = self.expressions.compile_all(&mut self.errors);
+                let id = ExpressionId(self.expressions.max_id - 1);
+                self.graph_caches.insert(id, Cache::new());
+                self.order.push(id);
+                self.expr_state
+                    .styles
+                    .insert(id, Style::nth(self.expr_state.styles.len()));
+                if let Some(latex) = self.expressions.storage.get(&id) {
+                    self.dependencies.update(id, latex);
+                }
+                // A new definition can fix expressions that used it before it existed, or conflict
+                // with another definition of the same name.
+                let affected = self
+                    .dependencies
+                    .dependents(self.dependencies.same_name(id));
+                self.refresh(&affected);
                 return focus(Id::new(format!("equation_{}", self.expressions.max_id - 1)));
             }
+            Message::DomainChanged(i, domain) => {
+                self.expr_state.domains.insert(i, domain);
+                self.check_domain(i);
+                self.graph_caches[&i].clear();
+            }
+            Message::SliderMoved(i, value) => {
+                let mut affected = HashSet::new();
+                self.set_slider(i, value, &mut affected);
+                self.refresh(&affected);
+                if let Some(animation) = self.expr_state.animations.get_mut(&i) {
+                    animation.value = value;
+                }
+            }
+            Message::SliderChanged(i, slider) => {
+                self.expr_state.sliders.insert(i, slider);
+            }
+            Message::TogglePlaying(i) => {
+                if self.expr_state.animations.remove(&i).is_none() {
+                    let value = self
+                        .expressions
+                        .storage
+                        .get(&i)
+                        .and_then(|latex| variables::number_assignment(latex));
+                    let animation = value.and_then(|(_, value)| {
+                        self.expr_state
+                            .sliders
+                            .get(&i)
+                            .cloned()
+                            .unwrap_or_default()
+                            .play(value)
+                    });
+                    if let Some(animation) = animation {
+                        self.expr_state.animations.insert(i, animation);
+                    }
+                }
+                if self.expr_state.animations.is_empty() {
+                    self.last_tick = None;
+                }
+            }
+            Message::Tick(now) => {
+                // A late frame moves sliders further, but a long pause doesn't make them jump.
+                let seconds = self
+                    .last_tick
+                    .map_or(0.0, |last| (now - last).as_secs_f32().min(0.1));
+                self.last_tick = Some(now);
+
+                // Sliders sharing dependents would otherwise each recompile them every frame.
+                let mut affected = HashSet::new();
+                let ids: Vec<ExpressionId> = self.expr_state.animations.keys().copied().collect();
+                for i in ids {
+                    let slider = self.expr_state.sliders.get(&i).cloned().unwrap_or_default();
+                    let Some(animation) = self.expr_state.animations.get_mut(&i) else {
+                        continue;
+                    };
+                    let playing = slider.advance(animation, seconds);
+                    let value = slider.snap(animation.value);
+                    if !self.set_slider(i, value, &mut affected) || !playing {
+                        self.expr_state.animations.remove(&i);
+                    }
+                }
+                self.refresh(&affected);
+                if self.expr_state.animations.is_empty() {
+                    self.last_tick = None;
+                }
+            }
+            Message::StyleChanged(i, style) => {
+                self.expr_state.styles.insert(i, style);
+                self.graph_caches[&i].clear();
+            }
+            Message::ToggleHidden(i) => {
+                if !self.expr_state.hidden.remove(&i) {
+                    self.expr_state.hidden.insert(i);
+                    self.clear_traces(i);
+                }
+            }
+            Message::DragStart(id) => {
+                self.drag = Some(Drag { id, over: id });
+            }
+            Message::DragHover(over) => {
+                if let Some(drag) = &mut self.drag {
+                    drag.over = over;
+                }
+            }
+            Message::DragCancel => {
+                self.drag = None;
+            }
+            Message::DragDrop => {
+                if let Some(Drag { id, over }) = self.drag.take() {
+                    self.move_expression(id, over);
+                }
+            }
+            Message::Undo => {
+                if let Some(snapshot) = self.history.undo(self.snapshot()) {
+                    self.restore(snapshot);
+                }
+            }
+            Message::Redo => {
+                if let Some(snapshot) = self.history.redo(self.snapshot()) {
+                    self.restore(snapshot);
+                }
+            }
+            Message::Open => return Task::perform(file::open(), Message::Opened),
+            Message::Load(url) => return self.fetch(url),
+            Message::Loaded(url, state) => {
+                let document = state.and_then(|state| {
+                    Document::from_json(state).map_err(|e| LoadError::Shape {
+                        url: url.clone(),
+                        message: e.to_string(),
+                    })
+                });
+                match document {
+                    Ok(document) => {
+                        self.load(document);
+                        self.banner = None;
+                    }
+                    Err(e) => {
+                        self.banner = Some(Banner::Error {
+                            message: e.to_string(),
+                            retry: Some(url),
+                        })
+                    }
+                }
+            }
+            Message::DismissBanner => {
+                self.banner = None;
+            }
+            Message::Opened(Ok((path, state))) => match Document::from_json(state) {
+                Ok(document) => {
+                    self.load(document);
+                    self.file = Some(path);
+                }
+                Err(e) => {
+                    self.banner = Some(Banner::Error {
+                        message: format!("{} is not a valid graph: {}", path.display(), e),
+                        retry: None,
+                    })
+                }
+            },
+            Message::Save => {
+                return Task::perform(
+                    file::save(self.file.clone(), self.document().to_json()),
+                    Message::Saved,
+                )
+            }
+            Message::Saved(Ok(path)) => {
+                self.file = Some(path);
+            }
+            Message::Opened(Err(FileError::DialogClosed))
+            | Message::Saved(Err(FileError::DialogClosed)) => (),
+            Message::Opened(Err(e)) | Message::Saved(Err(e)) => {
+                self.banner = Some(Banner::Error {
+                    message: e.to_string(),
+                    retry: None,
+                })
+            }
+            Message::EditStyle(i) => {
+                self.style_editor = i;
+            }
+            Message::EquationRemoved(i) => {
+                // Anything that used a definition from the removed expression changes too, as do
+                // other definitions of the same name it was in conflict with.
+                let affected = self.dependencies.dependents(self.dependencies.same_name(i));
+                self.dependencies.remove(i);
+                self.expressions.storage.remove(&i);
+                self.order.retain(|id| *id != i);
+                self.graph_caches.remove(&i);
+                self.expr_state.domains.remove(&i);
+                self.expr_state.sliders.remove(&i);
+                self.expr_state.animations.remove(&i);
+                self.expr_state.styles.remove(&i);
+                self.expr_state.hidden.remove(&i);
+                self.expr_state.draw_errors.borrow_mut().remove(&i);
+                self.graph_view.samples.borrow_mut().remove(&i);
+                self.graph_view.analysis.borrow_mut().remove(i);
+                self.clear_traces(i);
+                if self.style_editor == Some(i) {
+                    self.style_editor = None;
+                }
+                if self.shown_error == Some(i) {
+                    self.shown_error = None;
+                }
+                self.refresh(&affected);
+            }
             Message::Scaled(scale, mid) => {
-                self.scale = scale;
+                self.graph_view.scale = scale;
                 if let Some(mid) = mid {
-                    self.mid = mid;
+                    self.graph_view.mid = mid;
                 }
                 self.clear_caches();
             }
@@ -236,6 +954,38 @@ impl Somsed {
             Message::FocusExpr(i) => return focus(Id::new(format!("equation_{}", i))),
             Message::Resized(ResizeEvent { split, ratio }) => {
                 self.panes.resize(split, ratio);
+                self.resize_graph();
+            }
+            Message::GraphResized(size) => {
+                self.graph_view.size = size;
+                self.fit_viewport();
+            }
+            Message::WindowResized(size) => {
+                self.window_size = Some(size);
+                self.resize_graph();
+            }
+            Message::SettingsChanged(settings) => self.change_settings(settings),
+            Message::Hover(trace) => {
+                self.graph_view.hover = trace;
+            }
+            Message::Pin(trace) => {
+                self.graph_view.pinned = trace;
+            }
+            Message::BoundsChanged(bounds) => {
+                self.bounds_input = Some(bounds);
+            }
+            Message::BoundsSubmitted => {
+                let fit = self
+                    .bounds_input
+                    .as_ref()
+                    .and_then(Bounds::parse)
+                    .and_then(|v| v.fit(self.graph_view.size, false, self.settings.axes));
+                if let Some((scale, mid)) = fit {
+                    self.graph_view.scale = scale;
+                    self.graph_view.mid = mid;
+                    self.bounds_input = None;
+                    self.clear_caches();
+                }
             }
         };
         Task::none()
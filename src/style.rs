@@ -0,0 +1,116 @@
+use iced::{
+    widget::canvas::{LineCap, LineDash, Stroke},
+    Color,
+};
+
+/// The colors Desmos rotates through for new expressions.
+pub const PALETTE: [Color; 6] = [
+    Color::from_rgb(0.780, 0.267, 0.251),
+    Color::from_rgb(0.176, 0.439, 0.702),
+    Color::from_rgb(0.220, 0.549, 0.275),
+    Color::from_rgb(0.376, 0.259, 0.651),
+    Color::from_rgb(0.980, 0.494, 0.098),
+    Color::from_rgb(0.0, 0.0, 0.0),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineStyle {
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+impl LineStyle {
+    pub const ALL: [LineStyle; 3] = [LineStyle::Solid, LineStyle::Dashed, LineStyle::Dotted];
+
+    /// Parses the `lineStyle` field of a Desmos expression.
+    pub fn from_desmos(s: &str) -> Option<Self> {
+        match s {
+            "SOLID" => Some(LineStyle::Solid),
+            "DASHED" => Some(LineStyle::Dashed),
+            "DOTTED" => Some(LineStyle::Dotted),
+            _ => None,
+        }
+    }
+
+    pub fn to_desmos(self) -> &'static str {
+        match self {
+            LineStyle::Solid => "SOLID",
+            LineStyle::Dashed => "DASHED",
+            LineStyle::Dotted => "DOTTED",
+        }
+    }
+
+    fn dash(self) -> LineDash<'static> {
+        let segments: &'static [f32] = match self {
+            LineStyle::Solid => &[],
+            LineStyle::Dashed => &[10.0, 6.0],
+            LineStyle::Dotted => &[0.1, 6.0],
+        };
+        LineDash {
+            segments,
+            offset: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Style {
+    pub color: Color,
+    pub width: f32,
+    pub line_style: LineStyle,
+    pub opacity: f32,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self::nth(1)
+    }
+}
+
+impl Style {
+    /// The default style of the `n`th expression, cycling through the [`PALETTE`].
+    pub fn nth(n: usize) -> Self {
+        Self {
+            color: PALETTE[n % PALETTE.len()],
+            width: 2.5,
+            line_style: LineStyle::Solid,
+            opacity: 0.9,
+        }
+    }
+
+    pub fn stroke(&self) -> Stroke<'static> {
+        Stroke {
+            line_dash: self.line_style.dash(),
+            line_cap: LineCap::Round,
+            ..Stroke::default().with_width(self.width).with_color(Color {
+                a: self.opacity,
+                ..self.color
+            })
+        }
+    }
+
+    /// The color regions like inequalities are filled with.
+    pub fn fill(&self) -> Color {
+        Color {
+            a: self.opacity * 0.4,
+            ..self.color
+        }
+    }
+}
+
+/// Parses a `#rrggbb` color.
+pub fn parse_color(s: &str) -> Option<Color> {
+    let hex = s.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some(Color::from_rgb8(channel(0)?, channel(2)?, channel(4)?))
+}
+
+pub fn format_color(color: Color) -> String {
+    let [r, g, b, _] = color.into_rgba8();
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
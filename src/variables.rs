@@ -0,0 +1,534 @@
+use std::collections::{HashMap, HashSet};
+
+use desmoxide::graph::expressions::ExpressionId;
+
+use crate::graph::parse_bound;
+
+/// Names that are never defined by the user, and so never get a slider.
+const RESERVED: [&str; 6] = ["x", "y", "t", "r", "e", "\\theta"];
+
+/// Greek letters that name variables. `\pi` is left out, since it is a constant.
+const GREEK: [&str; 29] = [
+    "alpha", "beta", "gamma", "delta", "epsilon", "zeta", "eta", "theta", "iota", "kappa",
+    "lambda", "mu", "nu", "xi", "rho", "sigma", "tau", "upsilon", "phi", "chi", "psi", "omega",
+    "Gamma", "Delta", "Lambda", "Sigma", "Phi", "Psi", "Omega",
+];
+
+/// Seconds a slider takes to sweep its range when played at normal speed.
+const SWEEP_SECONDS: f32 = 4.0;
+
+/// Commands whose braced argument is a name, not variables.
+const NAMED: [&str; 3] = ["operatorname", "mathrm", "text"];
+
+/// What `name = body` or `name(params) = body` defines.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Definition<'a> {
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: &'a str,
+}
+
+/// What a playing slider does when it reaches the end of its range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoopMode {
+    #[default]
+    BackAndForth,
+    /// Jumps back to the start.
+    Loop,
+    /// Stops.
+    Once,
+}
+
+impl LoopMode {
+    pub const ALL: [LoopMode; 3] = [LoopMode::BackAndForth, LoopMode::Loop, LoopMode::Once];
+
+    /// Parses the `loopMode` field of a Desmos slider.
+    pub fn from_desmos(s: &str) -> Option<Self> {
+        match s {
+            "LOOP_FORWARD_REVERSE" => Some(LoopMode::BackAndForth),
+            "LOOP_FORWARD" => Some(LoopMode::Loop),
+            "PLAY_ONCE" => Some(LoopMode::Once),
+            _ => None,
+        }
+    }
+
+    pub fn to_desmos(self) -> &'static str {
+        match self {
+            LoopMode::BackAndForth => "LOOP_FORWARD_REVERSE",
+            LoopMode::Loop => "LOOP_FORWARD",
+            LoopMode::Once => "PLAY_ONCE",
+        }
+    }
+}
+
+/// The range and step of a slider, as typed into the sidebar, and how it plays. An empty step
+/// lets the slider move freely.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Slider {
+    pub min: String,
+    pub max: String,
+    pub step: String,
+    pub mode: LoopMode,
+    /// How many times faster than normal the slider plays.
+    pub speed: f32,
+}
+
+impl Default for Slider {
+    fn default() -> Self {
+        Self {
+            min: "-10".to_string(),
+            max: "10".to_string(),
+            step: String::new(),
+            mode: LoopMode::default(),
+            speed: 1.0,
+        }
+    }
+}
+
+/// A slider that is playing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Animation {
+    /// Where the slider is, before being rounded to its step.
+    pub value: f32,
+    /// 1 while moving up, -1 while moving down.
+    pub direction: f32,
+}
+
+impl Slider {
+    /// A slider with room for `value`, widening the default range to the next power of ten.
+    pub fn around(value: f32) -> Self {
+        if (-10.0..=10.0).contains(&value) || !value.is_finite() {
+            return Self::default();
+        }
+        let bound = 10f32.powi(value.abs().log10().ceil() as i32);
+        Self {
+            min: format!("{}", -bound),
+            max: format!("{bound}"),
+            ..Self::default()
+        }
+    }
+
+    pub fn range(&self) -> Option<(f32, f32)> {
+        let (min, max) = (parse_bound(&self.min)?, parse_bound(&self.max)?);
+        (min < max).then_some((min, max))
+    }
+
+    pub fn step(&self) -> Option<f32> {
+        parse_bound(&self.step).filter(|step| *step > 0.0)
+    }
+
+    /// Starts playing from `value`, or from the start if a slider that plays once is at the end.
+    pub fn play(&self, value: f32) -> Option<Animation> {
+        let (min, max) = self.range()?;
+        let value = match self.mode {
+            LoopMode::Once if value >= max => min,
+            _ => value.clamp(min, max),
+        };
+        Some(Animation {
+            value,
+            direction: 1.0,
+        })
+    }
+
+    /// Moves a playing slider along by `seconds`, returning whether it is still playing.
+    pub fn advance(&self, animation: &mut Animation, seconds: f32) -> bool {
+        let Some((min, max)) = self.range() else {
+            return false;
+        };
+        let value = animation.value
+            + animation.direction * (max - min) * self.speed * seconds / SWEEP_SECONDS;
+        animation.value = match self.mode {
+            LoopMode::Loop => min + (value - min).rem_euclid(max - min),
+            LoopMode::Once => value.min(max),
+            LoopMode::BackAndForth if value > max => {
+                animation.direction = -1.0;
+                (2.0 * max - value).max(min)
+            }
+            LoopMode::BackAndForth if value < min => {
+                animation.direction = 1.0;
+                (2.0 * min - value).min(max)
+            }
+            LoopMode::BackAndForth => value,
+        };
+        self.mode != LoopMode::Once || animation.value < max
+    }
+
+    /// Rounds `value` to a whole number of steps from the start of the range.
+    pub fn snap(&self, value: f32) -> f32 {
+        match (self.step(), self.range()) {
+            (Some(step), Some((min, max))) => {
+                (min + ((value - min) / step).round() * step).clamp(min, max)
+            }
+            _ => value,
+        }
+    }
+
+    /// Formats a value the slider was dragged to, with as many decimals as the step has, or a
+    /// thousandth of the range without one.
+    pub fn format(&self, value: f32) -> String {
+        let precision = match (self.step(), self.range()) {
+            (Some(step), _) => step,
+            (None, Some((min, max))) => (max - min) / 1000.0,
+            (None, None) => 0.001,
+        };
+        let decimals = (-precision.log10().floor()).clamp(0.0, 10.0) as usize;
+        let formatted = format!("{value:.decimals$}");
+        match formatted.trim_start_matches('-').trim_matches(['0', '.']) {
+            "" => "0".to_string(),
+            _ => formatted,
+        }
+    }
+}
+
+/// The names `latex` refers to, in order of first use, like `a`, `a_{1}` or `\alpha`.
+/// Subscripts are normalized to braces.
+pub fn identifiers(latex: &str) -> Vec<String> {
+    let mut found: Vec<String> = Vec::new();
+    let mut rest = latex;
+    while let Some(c) = rest.chars().next() {
+        if let Some((name, after)) = identifier(rest) {
+            if !found.contains(&name) {
+                found.push(name);
+            }
+            rest = after;
+        } else if let Some(command) = rest.strip_prefix('\\') {
+            let end = command
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(command.len());
+            rest = if NAMED.contains(&&command[..end]) {
+                skip_group(&command[end..])
+            } else {
+                &command[end..]
+            };
+        } else {
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    found
+}
+
+/// Reads the name at the start of `latex`, returning it and what follows.
+fn identifier(latex: &str) -> Option<(String, &str)> {
+    let (mut name, rest) = if let Some(command) = latex.strip_prefix('\\') {
+        let end = command
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(command.len());
+        GREEK
+            .contains(&&command[..end])
+            .then(|| (format!("\\{}", &command[..end]), &command[end..]))?
+    } else if let Some(rest) = latex.strip_prefix('θ') {
+        ("\\theta".to_string(), rest)
+    } else {
+        let c = latex.chars().next().filter(char::is_ascii_alphabetic)?;
+        (c.to_string(), &latex[1..])
+    };
+
+    let Some(subscript) = rest.strip_prefix('_') else {
+        return Some((name, rest));
+    };
+    let (subscript, rest) = match subscript.strip_prefix('{') {
+        Some(braced) => braced.split_once('}')?,
+        None => {
+            let c = subscript
+                .chars()
+                .next()
+                .filter(char::is_ascii_alphanumeric)?;
+            subscript.split_at(c.len_utf8())
+        }
+    };
+    name.push_str(&format!("_{{{subscript}}}"));
+    Some((name, rest))
+}
+
+/// Skips a braced group at the start of `latex`, if there is one.
+fn skip_group(latex: &str) -> &str {
+    let Some(group) = latex.strip_prefix('{') else {
+        return latex;
+    };
+    let mut depth = 1;
+    for (i, c) in group.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            _ => (),
+        }
+        if depth == 0 {
+            return &group[i + 1..];
+        }
+    }
+    ""
+}
+
+/// Reads `latex` as the definition of a variable or function. `y = ...`, `x = ...` and `r = ...`
+/// are curves, not definitions.
+pub fn definition(latex: &str) -> Option<Definition<'_>> {
+    let (lhs, body) = latex.split_once('=')?;
+    let (name, rest) = identifier(lhs.trim_start())?;
+    if RESERVED.contains(&name.as_str()) {
+        return None;
+    }
+
+    let rest = rest.trim();
+    let params = if rest.is_empty() {
+        Vec::new()
+    } else {
+        let inner = rest
+            .strip_prefix("\\left(")
+            .and_then(|s| s.strip_suffix("\\right)"))
+            .or_else(|| rest.strip_prefix('(')?.strip_suffix(')'))?;
+        inner
+            .split(',')
+            .map(|param| match identifier(param.trim()) {
+                Some((name, "")) => Some(name),
+                _ => None,
+            })
+            .collect::<Option<_>>()?
+    };
+    Some(Definition { name, params, body })
+}
+
+/// The variable and value of an expression like `a = 3`, which gets a slider.
+pub fn number_assignment(latex: &str) -> Option<(String, f32)> {
+    let definition = definition(latex)?;
+    let body = definition.body.trim();
+    let is_number = !body.is_empty()
+        && body
+            .trim_start_matches('-')
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == '.');
+    if !definition.params.is_empty() || !is_number {
+        return None;
+    }
+    let value = body.parse().ok().filter(|v: &f32| v.is_finite())?;
+    Some((definition.name, value))
+}
+
+/// The names an expression depends on, leaving out the parameters of a function definition and
+/// the name being defined.
+pub fn uses(latex: &str) -> Vec<String> {
+    match definition(latex) {
+        Some(definition) => identifiers(definition.body)
+            .into_iter()
+            .filter(|name| *name != definition.name && !definition.params.contains(name))
+            .collect(),
+        None => identifiers(latex),
+    }
+}
+
+/// The names `latex` uses that nothing in `storage` defines.
+pub fn undefined(latex: &str, storage: &HashMap<ExpressionId, String>) -> Vec<String> {
+    let defined: HashSet<String> = storage
+        .values()
+        .filter_map(|latex| Some(definition(latex)?.name))
+        .collect();
+    uses(latex)
+        .into_iter()
+        .filter(|name| !RESERVED.contains(&name.as_str()) && !defined.contains(name))
+        .collect()
+}
+
+/// What each expression defines and uses, so an edit only has to recompile and redraw the
+/// expressions it affects.
+#[derive(Debug, Default)]
+pub struct DependencyGraph {
+    defines: HashMap<ExpressionId, String>,
+    uses: HashMap<ExpressionId, Vec<String>>,
+}
+
+impl DependencyGraph {
+    pub fn new(storage: &HashMap<ExpressionId, String>) -> Self {
+        let mut graph = Self::default();
+        for (i, latex) in storage {
+            graph.update(*i, latex);
+        }
+        graph
+    }
+
+    /// Rescans `i` after its latex changed.
+    pub fn update(&mut self, i: ExpressionId, latex: &str) {
+        match definition(latex) {
+            Some(definition) => self.defines.insert(i, definition.name),
+            None => self.defines.remove(&i),
+        };
+        self.uses.insert(i, uses(latex));
+    }
+
+    pub fn remove(&mut self, i: ExpressionId) {
+        self.defines.remove(&i);
+        self.uses.remove(&i);
+    }
+
+    /// `ids` together with the expressions that use what they define, directly or through other
+    /// definitions.
+    pub fn dependents(&self, ids: impl IntoIterator<Item = ExpressionId>) -> HashSet<ExpressionId> {
+        let mut found: HashSet<ExpressionId> = ids.into_iter().collect();
+        let mut names: Vec<&String> = found.iter().filter_map(|i| self.defines.get(i)).collect();
+        while let Some(name) = names.pop() {
+            for (i, uses) in &self.uses {
+                if !found.contains(i) && uses.contains(name) {
+                    found.insert(*i);
+                    names.extend(self.defines.get(i));
+                }
+            }
+        }
+        found
+    }
+
+    /// `i` together with the other expressions defining the same name, which is an error they all
+    /// show until only one is left.
+    pub fn same_name(&self, i: ExpressionId) -> Vec<ExpressionId> {
+        let mut found = vec![i];
+        if let Some(name) = self.defines.get(&i) {
+            found.extend(
+                self.defines
+                    .iter()
+                    .filter(|(other, defined)| **other != i && *defined == name)
+                    .map(|(other, _)| *other),
+            );
+        }
+        found
+    }
+
+    /// `ids` together with the definitions they use, directly or through other definitions.
+    /// Every definition of a name that is defined more than once is included, so the conflict
+    /// gets reported.
+    pub fn dependencies(
+        &self,
+        ids: impl IntoIterator<Item = ExpressionId>,
+    ) -> HashSet<ExpressionId> {
+        let mut definers: HashMap<&String, Vec<ExpressionId>> = HashMap::new();
+        for (i, name) in &self.defines {
+            definers.entry(name).or_default().push(*i);
+        }
+        let mut found: HashSet<ExpressionId> = HashSet::new();
+        let mut pending: Vec<ExpressionId> = ids.into_iter().collect();
+        while let Some(i) = pending.pop() {
+            if !found.insert(i) {
+                continue;
+            }
+            if let Some(uses) = self.uses.get(&i) {
+                pending.extend(uses.iter().filter_map(|name| definers.get(name)).flatten());
+            }
+        }
+        found
+    }
+}
+
+/// A name as shown next to its slider, like `a_1` for `a_{1}`.
+pub fn display(name: &str) -> String {
+    name.trim_start_matches('\\').replace(['{', '}'], "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(ids: &[usize]) -> HashSet<ExpressionId> {
+        ids.iter().map(|i| ExpressionId(*i as _)).collect()
+    }
+
+    fn graph(latex: &[&str]) -> DependencyGraph {
+        let storage: HashMap<ExpressionId, String> = latex
+            .iter()
+            .enumerate()
+            .map(|(i, latex)| (ExpressionId(i as _), latex.to_string()))
+            .collect();
+        DependencyGraph::new(&storage)
+    }
+
+    #[test]
+    fn identifiers_in_order() {
+        assert_eq!(
+            identifiers("a_{1}+\\alpha b_2\\cdot a_1"),
+            vec!["a_{1}", "\\alpha", "b_{2}"]
+        );
+        assert_eq!(identifiers("\\sin\\left(\\pi x\\right)"), vec!["x"]);
+        assert_eq!(identifiers("\\operatorname{floor}(c)"), vec!["c"]);
+    }
+
+    #[test]
+    fn definitions() {
+        assert_eq!(
+            definition("f\\left(x,y\\right)=x+y"),
+            Some(Definition {
+                name: "f".to_string(),
+                params: vec!["x".to_string(), "y".to_string()],
+                body: "x+y",
+            })
+        );
+        assert_eq!(definition("y=2x"), None);
+        assert_eq!(definition("x^2+y^2=1"), None);
+        assert_eq!(uses("f(x)=x^2+a"), vec!["a"]);
+    }
+
+    #[test]
+    fn number_assignments() {
+        assert_eq!(number_assignment("a=-1.5"), Some(("a".to_string(), -1.5)));
+        assert_eq!(
+            number_assignment("b_{1} = 3"),
+            Some(("b_{1}".to_string(), 3.0))
+        );
+        assert_eq!(number_assignment("a=2b"), None);
+        assert_eq!(number_assignment("f(x)=3"), None);
+        assert_eq!(number_assignment("y=3"), None);
+    }
+
+    #[test]
+    fn undefined_names() {
+        let storage = HashMap::from([(ExpressionId(0), "a=1".to_string())]);
+        assert_eq!(undefined("y=ax+b\\theta", &storage), vec!["b"]);
+    }
+
+    #[test]
+    fn dependents_follow_chains() {
+        let graph = graph(&["a=1", "b=a+1", "y=bx", "c=2"]);
+        assert_eq!(graph.dependents(ids(&[0])), ids(&[0, 1, 2]));
+        assert_eq!(graph.dependents(ids(&[1])), ids(&[1, 2]));
+        assert_eq!(graph.dependents(ids(&[3])), ids(&[3]));
+    }
+
+    #[test]
+    fn dependencies_follow_chains() {
+        let graph = graph(&["a=1", "b=a+1", "y=bx", "c=2"]);
+        assert_eq!(graph.dependencies(ids(&[2])), ids(&[0, 1, 2]));
+        assert_eq!(graph.dependencies(ids(&[0])), ids(&[0]));
+    }
+
+    #[test]
+    fn updates_move_edges() {
+        let mut graph = graph(&["a=1", "b=a+1", "y=bx", "c=2"]);
+        graph.update(ExpressionId(1), "b=c");
+        assert_eq!(graph.dependents(ids(&[0])), ids(&[0]));
+        assert_eq!(graph.dependents(ids(&[3])), ids(&[1, 2, 3]));
+        assert_eq!(graph.dependencies(ids(&[2])), ids(&[1, 2, 3]));
+
+        graph.remove(ExpressionId(1));
+        assert_eq!(graph.dependents(ids(&[3])), ids(&[3]));
+    }
+
+    #[test]
+    fn names_defined_twice() {
+        let graph = graph(&["a=1", "y=ax", "a=2"]);
+        let same: HashSet<ExpressionId> = graph.same_name(ExpressionId(0)).into_iter().collect();
+        assert_eq!(same, ids(&[0, 2]));
+        assert_eq!(graph.dependencies(ids(&[1])), ids(&[0, 1, 2]));
+    }
+
+    #[test]
+    fn sliders_bounce_and_snap() {
+        let slider = Slider {
+            step: "0.5".to_string(),
+            ..Slider::default()
+        };
+        let mut animation = Animation {
+            value: 9.5,
+            direction: 1.0,
+        };
+        assert!(slider.advance(&mut animation, 0.2));
+        assert_eq!(animation.value, 9.5);
+        assert_eq!(animation.direction, -1.0);
+        assert_eq!(slider.snap(3.3), 3.5);
+        assert_eq!(slider.format(3.5), "3.5");
+        assert_eq!(Slider::around(42.0).range(), Some((-100.0, 100.0)));
+    }
+}